@@ -0,0 +1,163 @@
+//! Compares the snake's hot paths with the hash-based snake they replaced.
+//! Run with `cargo bench`.
+
+use snake_game::config::Config;
+use snake_game::simulation::{Direction, FreeCells, Simulation, Snake};
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+use glam::IVec2;
+
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+use std::collections::{HashMap, HashSet, LinkedList};
+
+const SNAKE_LEN: usize = 200;
+const MOVES: usize = 1000;
+
+/// The snake before the grid redesign: a linked list, a set of squares and
+/// a hashed refcount over every segment's off-limits neighbourhood.
+struct HashSnake {
+    body: LinkedList<IVec2>,
+    set: HashSet<IVec2>,
+    occupied: HashMap<IVec2, u8>,
+    off_limits_range: i32,
+}
+
+impl HashSnake {
+    fn new(start_pos: IVec2, off_limits_range: i32) -> Self {
+        let mut snake = Self {
+            body: LinkedList::new(),
+            set: HashSet::new(),
+            occupied: HashMap::new(),
+            off_limits_range,
+        };
+        snake.grow(start_pos);
+        snake
+    }
+
+    fn grow(&mut self, pos: IVec2) -> bool {
+        if self.set.contains(&pos) && *self.body.front().unwrap() != pos {
+            return false;
+        }
+        self.body.push_back(pos);
+        self.set.insert(pos);
+        let range = self.off_limits_range;
+        for x in -range..=range {
+            for y in -range..=range {
+                *self.occupied.entry(pos + IVec2::new(x, y)).or_insert(0) += 1;
+            }
+        }
+        true
+    }
+
+    fn shrink(&mut self) {
+        let elem = self.body.pop_front().unwrap();
+        self.set.remove(&elem);
+        let range = self.off_limits_range;
+        for x in -range..=range {
+            for y in -range..=range {
+                let pos = elem + IVec2::new(x, y);
+                let count = self.occupied.get_mut(&pos).unwrap();
+                *count -= 1;
+                if *count == 0 {
+                    self.occupied.remove(&pos);
+                }
+            }
+        }
+    }
+}
+
+/// Every square of the board once, in a cycle that can be walked in a
+/// loop: back and forth along the rows, leaving out the first column, then
+/// back up it. That needs an even number of rows, so with an odd number
+/// the board is walked by columns instead. No cycle covers a board with an
+/// odd number of both.
+fn tour(dimensions: IVec2) -> Vec<IVec2> {
+    if dimensions.y % 2 == 1 {
+        assert!(dimensions.x % 2 == 0, "an odd by odd board has no tour");
+        let flip = |pos: IVec2| IVec2::new(pos.y, pos.x);
+        return tour(flip(dimensions)).into_iter().map(flip).collect();
+    }
+    let mut path = Vec::new();
+    for y in 0..dimensions.y {
+        if y % 2 == 0 {
+            path.extend((1..dimensions.x).map(|x| IVec2::new(x, y)));
+        } else {
+            path.extend((1..dimensions.x).rev().map(|x| IVec2::new(x, y)));
+        }
+    }
+    path.extend((0..dimensions.y).rev().map(|y| IVec2::new(0, y)));
+    path
+}
+
+fn grow_shrink(c: &mut Criterion) {
+    let config = Config::default();
+    let path = tour(config.dimensions);
+    let mut group = c.benchmark_group("grow_shrink");
+
+    group.bench_function("hash", |b| {
+        let mut snake = HashSnake::new(path[0], config.off_limits_range);
+        for pos in &path[1..SNAKE_LEN] {
+            snake.grow(*pos);
+        }
+        let mut next = SNAKE_LEN;
+        b.iter(|| {
+            for _ in 0..MOVES {
+                black_box(snake.grow(path[next % path.len()]));
+                snake.shrink();
+                next += 1;
+            }
+        });
+    });
+
+    group.bench_function("grid", |b| {
+        let mut free_cells = FreeCells::new(config.dimensions);
+        let mut snake = Snake::new(
+            path[0],
+            config.dimensions,
+            config.off_limits_range,
+            config.wrap,
+            &mut free_cells,
+        );
+        for pos in &path[1..SNAKE_LEN] {
+            snake.grow(*pos, &mut free_cells);
+        }
+        let mut next = SNAKE_LEN;
+        b.iter(|| {
+            for _ in 0..MOVES {
+                black_box(snake.grow(path[next % path.len()], &mut free_cells));
+                snake.shrink(&mut free_cells);
+                next += 1;
+            }
+        });
+    });
+
+    group.finish();
+}
+
+/// Whole games with random turns, as a bot would play them.
+fn simulate(c: &mut Criterion) {
+    c.bench_function("simulate", |b| {
+        let mut seed = 0;
+        b.iter(|| {
+            let mut sim = Simulation::new(seed, Config::default());
+            let mut rng = ChaCha8Rng::seed_from_u64(seed);
+            while sim.live && sim.tick < 100_000 {
+                let input = if rng.gen_range(0..8) == 0 {
+                    let index = rng.gen_range(0..Direction::ALL.len());
+                    Some(Direction::ALL[index])
+                } else {
+                    None
+                };
+                sim.step(input);
+            }
+            seed += 1;
+            black_box(sim.score)
+        });
+    });
+}
+
+criterion_group!(benches, grow_shrink, simulate);
+criterion_main!(benches);
@@ -0,0 +1,323 @@
+use crate::grid::Grid;
+use crate::simulation::{Direction, Simulation};
+
+use glam::IVec2;
+
+use std::collections::VecDeque;
+
+/// Plays the game by itself. Each move it heads for the most valuable fruit
+/// it can reach and still escape from afterwards, and otherwise stalls in
+/// whichever direction leaves it the most room.
+#[derive(Default)]
+pub struct Autopilot {
+    /// The head position the last decision was made from.
+    decided_at: Option<IVec2>,
+}
+
+/// What the board will look like after the snake has made some moves.
+struct Board {
+    /// How many moves until each square is free. Walls never are.
+    free_after: Grid<u32>,
+}
+
+impl Autopilot {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The turn to make before the snake's next move, if any. Decides once
+    /// per move, after any turns already queued have been used up.
+    pub fn next_input(&mut self, sim: &Simulation) -> Option<Direction> {
+        if !sim.live || !sim.queued_turns.is_empty() {
+            return None;
+        }
+        let head = sim.snake.head();
+        if self.decided_at == Some(head) && sim.started() {
+            return None;
+        }
+        self.decided_at = Some(head);
+        let direction = choose(sim)?;
+        if sim.direction == Some(direction) {
+            None
+        } else {
+            Some(direction)
+        }
+    }
+}
+
+impl Board {
+    fn new(sim: &Simulation) -> Self {
+        let mut free_after = Grid::new(sim.config.dimensions, 0);
+        for pos in &sim.stone_walls {
+            *free_after.get_mut(*pos).unwrap() = u32::MAX;
+        }
+        // The segment `i` squares from the tail moves off once the tail has
+        // moved `i + 1` times, which waits for any growth still to come.
+        for (i, pos) in sim.snake.iter().enumerate() {
+            *free_after.get_mut(*pos).unwrap() = i as u32 + 1 + sim.grow_buffer;
+        }
+        Self { free_after }
+    }
+
+    /// Whether the head can be on `pos` after `moves` moves.
+    fn is_open(&self, pos: IVec2, moves: u32) -> bool {
+        self.free_after.get(pos).is_some_and(|&free_after| moves >= free_after)
+    }
+}
+
+/// The direction to move in next, or `None` if every move is fatal.
+fn choose(sim: &Simulation) -> Option<Direction> {
+    let board = Board::new(sim);
+    let mut targets = Vec::new();
+    if let Some(golden_apple) = sim.golden_apple {
+        targets.push((golden_apple, sim.config.golden_apple_worth));
+    }
+    if let (Some(orange), false) = (sim.orange, sim.shielded) {
+        targets.push((orange, 0));
+    }
+    targets.push((sim.apple, 1));
+
+    for (target, growth) in targets {
+        if let Some(path) = find_path(sim, &board, target) {
+            if is_safe(sim, &path, growth) {
+                return Some(path[0]);
+            }
+        }
+    }
+    stall(sim, &board)
+}
+
+/// The moves that can be made from the head right now: on the board, not
+/// into anything and never a reversal.
+fn first_moves<'a>(
+    sim: &'a Simulation,
+    board: &'a Board,
+) -> impl Iterator<Item = Direction> + 'a {
+    let head = sim.snake.head();
+    Direction::ALL.into_iter().filter(move |&direction| {
+        sim.direction != Some(direction.opposite()) &&
+            board.is_open(sim.next_square(head, direction), 1)
+    })
+}
+
+/// The shortest route to `target` as a list of moves, found by a
+/// breadth-first search that knows when each body square will be free.
+fn find_path(
+    sim: &Simulation,
+    board: &Board,
+    target: IVec2,
+) -> Option<Vec<Direction>> {
+    let head = sim.snake.head();
+    let mut came_from: Grid<Option<Direction>> =
+        Grid::new(sim.config.dimensions, None);
+    let mut queue = VecDeque::new();
+    for direction in first_moves(sim, board) {
+        let next = sim.next_square(head, direction);
+        *came_from.get_mut(next).unwrap() = Some(direction);
+        queue.push_back((next, 1));
+    }
+    while let Some((pos, moves)) = queue.pop_front() {
+        if pos == target {
+            let mut path = Vec::with_capacity(moves as usize);
+            let mut pos = pos;
+            while pos != head {
+                let direction = came_from.get(pos).unwrap().unwrap();
+                path.push(direction);
+                pos = sim.next_square(pos, direction.opposite());
+            }
+            path.reverse();
+            return Some(path);
+        }
+        for direction in Direction::ALL {
+            let next = sim.next_square(pos, direction);
+            if next != head &&
+                board.is_open(next, moves + 1) &&
+                came_from.get(next) == Some(&None)
+            {
+                *came_from.get_mut(next).unwrap() = Some(direction);
+                queue.push_back((next, moves + 1));
+            }
+        }
+    }
+    None
+}
+
+/// Whether the snake still has a way out after following `path` and
+/// growing by `growth`: it can reach its own tail, or at least has room
+/// for its whole body.
+fn is_safe(sim: &Simulation, path: &[Direction], growth: u32) -> bool {
+    let mut body: VecDeque<IVec2> = sim.snake.iter().copied().collect();
+    let mut to_grow = sim.grow_buffer;
+    let mut head = sim.snake.head();
+    for direction in path {
+        head = sim.next_square(head, *direction);
+        body.push_back(head);
+        if to_grow == 0 {
+            body.pop_front();
+        } else {
+            to_grow -= 1;
+        }
+    }
+    let length = body.len() + (to_grow + growth) as usize;
+
+    let mut blocked = Grid::new(sim.config.dimensions, false);
+    for pos in &sim.stone_walls {
+        *blocked.get_mut(*pos).unwrap() = true;
+    }
+    for pos in &body {
+        *blocked.get_mut(*pos).unwrap() = true;
+    }
+    let tail = *body.front().unwrap();
+    *blocked.get_mut(tail).unwrap() = false;
+    let room = flood_fill(sim, &mut blocked, head);
+    room >= length || room_reaches(&blocked, tail)
+}
+
+/// Counts the open squares reachable from `start`, marking each as blocked
+/// on the way.
+fn flood_fill(
+    sim: &Simulation,
+    blocked: &mut Grid<bool>,
+    start: IVec2,
+) -> usize {
+    let mut stack = vec![start];
+    let mut count = 0;
+    while let Some(pos) = stack.pop() {
+        for direction in Direction::ALL {
+            let next = sim.next_square(pos, direction);
+            if blocked.get(next) == Some(&false) {
+                *blocked.get_mut(next).unwrap() = true;
+                count += 1;
+                stack.push(next);
+            }
+        }
+    }
+    count
+}
+
+/// Whether a flood fill reached `pos`, which was left open before the fill.
+fn room_reaches(filled: &Grid<bool>, pos: IVec2) -> bool {
+    filled.get(pos) == Some(&true)
+}
+
+/// Picks the move that leaves the most room, preferring to keep going
+/// straight, for when no fruit can be reached safely.
+fn stall(sim: &Simulation, board: &Board) -> Option<Direction> {
+    let head = sim.snake.head();
+    first_moves(sim, board)
+        .map(|direction| {
+            let mut blocked = Grid::new(sim.config.dimensions, false);
+            for pos in &sim.stone_walls {
+                *blocked.get_mut(*pos).unwrap() = true;
+            }
+            for pos in sim.snake.iter().skip(1) {
+                *blocked.get_mut(*pos).unwrap() = true;
+            }
+            let next = sim.next_square(head, direction);
+            *blocked.get_mut(next).unwrap() = true;
+            let room = flood_fill(sim, &mut blocked, next);
+            (room, sim.direction == Some(direction), direction)
+        })
+        .max_by_key(|&(room, straight, _)| (room, straight))
+        .map(|(_, _, direction)| direction)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{Config, Fruit};
+    use crate::controller;
+
+    use std::collections::BTreeMap;
+
+    /// A small board where the snake moves on every step and nothing
+    /// spawns by itself, with the snake at (2, 2) and fruit placed.
+    fn sim(fruits: &[(Fruit, IVec2)], walls: &[IVec2]) -> Simulation {
+        let config = Config {
+            dimensions: IVec2::new(10, 10),
+            starting_frame_delay: 1,
+            off_limits_range: 1,
+            spawns: Vec::new(),
+            start: Some(IVec2::new(2, 2)),
+            walls: walls.to_vec(),
+            fruits: fruits.iter().copied().collect::<BTreeMap<_, _>>(),
+            ..Config::default()
+        };
+        Simulation::new(1, config)
+    }
+
+    #[test]
+    fn heads_for_a_reachable_apple() {
+        let apple = IVec2::new(6, 5);
+        let mut sim = sim(&[(Fruit::Apple, apple)], &[]);
+        let board = Board::new(&sim);
+        let path = find_path(&sim, &board, apple).unwrap();
+        assert_eq!(path.len(), 7);
+        controller::play(&mut sim, &mut Autopilot::new(), 7).unwrap();
+        assert_eq!(sim.apples_eaten, 1);
+        assert!(sim.live);
+    }
+
+    #[test]
+    fn prefers_a_golden_apple_or_an_orange() {
+        let apple = (Fruit::Apple, IVec2::new(2, 5));
+        let far = IVec2::new(7, 2);
+        for fruit in [Fruit::GoldenApple, Fruit::Orange] {
+            let sim = sim(&[apple, (fruit, far)], &[]);
+            assert_eq!(choose(&sim), Some(Direction::RIGHT), "{:?}", fruit);
+        }
+        // An orange is no use while shielded.
+        let mut sim = sim(&[apple, (Fruit::Orange, far)], &[]);
+        sim.shielded = true;
+        assert_eq!(choose(&sim), Some(Direction::DOWN));
+    }
+
+    #[test]
+    fn keeps_out_of_a_pocket_too_small_for_it() {
+        // A dead end three squares deep, opening downwards, with the
+        // apple at the far end. Once the snake is long it cannot get out.
+        let walls: Vec<IVec2> = (0..3)
+            .flat_map(|y| [IVec2::new(5, y), IVec2::new(7, y)])
+            .collect();
+        let apple = IVec2::new(6, 0);
+        let mut sim = sim(&[(Fruit::Apple, apple)], &walls);
+        let board = Board::new(&sim);
+        let path = find_path(&sim, &board, apple).unwrap();
+        assert!(is_safe(&sim, &path, 1));
+
+        sim.grow_buffer = 6;
+        let board = Board::new(&sim);
+        let path = find_path(&sim, &board, apple).unwrap();
+        assert!(!is_safe(&sim, &path, 1));
+    }
+
+    #[test]
+    fn stalls_when_the_apple_is_out_of_reach() {
+        let walls = [IVec2::new(8, 9), IVec2::new(9, 8)];
+        let mut sim = sim(&[(Fruit::Apple, IVec2::new(9, 9))], &walls);
+        let board = Board::new(&sim);
+        assert_eq!(find_path(&sim, &board, sim.apple), None);
+        controller::play(&mut sim, &mut Autopilot::new(), 200).unwrap();
+        assert!(sim.live);
+        assert_eq!(sim.tick, 200);
+        assert_eq!(sim.apples_eaten, 0);
+    }
+
+    /// Well under what the autopilot scores now, so that only a real
+    /// drop in its play fails.
+    const MIN_SCORE: u32 = 30;
+
+    #[test]
+    fn scores_well_on_fixed_seeds() {
+        let config = Config {
+            dimensions: IVec2::new(20, 12),
+            ..Config::default()
+        };
+        for seed in 0..3 {
+            let mut sim = Simulation::new(seed, config.clone());
+            controller::play(&mut sim, &mut Autopilot::new(), 20_000)
+                .unwrap();
+            assert!(sim.score >= MIN_SCORE, "seed {}: {}", seed, sim.score);
+        }
+    }
+}
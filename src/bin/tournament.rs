@@ -0,0 +1,408 @@
+//! Plays the same seeded games with several bots and compares how they did.
+
+use snake_game::autopilot::Autopilot;
+use snake_game::bot::{BotError, StdioBot};
+use snake_game::config::Config;
+use snake_game::controller::{self, Controller};
+use snake_game::level::Level;
+use snake_game::simulation::{DeathCause, Simulation};
+
+use clap::Parser;
+
+use std::fmt::Write as _;
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// Runs seeded headless games for each bot and reports their scores, how
+/// long they survived and what killed them.
+#[derive(Parser)]
+#[command(name = "tournament", version)]
+struct Cli {
+    /// A bot to enter, as NAME=COMMAND for an external stdio bot or
+    /// `autopilot` for the built-in one. Repeat for each entrant. Defaults
+    /// to the autopilot alone.
+    #[arg(long = "bot", value_name = "ENTRANT")]
+    bots: Vec<String>,
+
+    /// Games per bot.
+    #[arg(long, default_value_t = 100)]
+    games: u32,
+
+    /// Seed of the first game. Each game uses the next seed after the one
+    /// before, and every bot plays the same seeds.
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
+
+    /// Gameplay config file.
+    #[arg(long, value_name = "PATH")]
+    config: Option<PathBuf>,
+
+    /// Named preset from the config file.
+    #[arg(long, value_name = "NAME", requires = "config")]
+    preset: Option<String>,
+
+    /// Level file to play instead of the open board.
+    #[arg(long, value_name = "PATH", conflicts_with = "config")]
+    level: Option<PathBuf>,
+
+    /// How long an external bot gets to answer each move.
+    #[arg(long, value_name = "MS", default_value_t = 100)]
+    bot_timeout: u64,
+
+    /// Frames after which a game that has not ended is stopped.
+    #[arg(long, default_value_t = 1_000_000)]
+    max_ticks: u64,
+
+    /// Also write the summary as CSV to this file.
+    #[arg(long, value_name = "PATH")]
+    csv: Option<PathBuf>,
+}
+
+enum Entrant {
+    Autopilot,
+    Stdio { name: String, command: String },
+}
+
+/// How one game ended.
+#[derive(Copy, Clone, PartialEq)]
+enum Ending {
+    Died(DeathCause),
+    Cleared,
+    /// Reached `--max-ticks` still alive.
+    Cutoff,
+    Disqualified,
+}
+
+struct GameResult {
+    score: u32,
+    ticks: u64,
+    ending: Ending,
+}
+
+struct Summary {
+    name: String,
+    games: usize,
+    mean_score: f64,
+    median_score: f64,
+    max_score: u32,
+    mean_ticks: f64,
+    /// How many games ended each way, in `Summary::ENDINGS` order.
+    endings: Vec<usize>,
+}
+
+impl Entrant {
+    fn parse(value: &str) -> Result<Self, String> {
+        if value == "autopilot" {
+            return Ok(Entrant::Autopilot);
+        }
+        match value.split_once('=') {
+            Some((name, command))
+                if !name.is_empty() && !command.is_empty() =>
+            {
+                Ok(Entrant::Stdio {
+                    name: name.to_string(),
+                    command: command.to_string(),
+                })
+            }
+            _ => Err(format!(
+                "expected NAME=COMMAND or autopilot, got '{}'",
+                value,
+            )),
+        }
+    }
+
+    fn name(&self) -> &str {
+        match self {
+            Entrant::Autopilot => "autopilot",
+            Entrant::Stdio { name, .. } => name,
+        }
+    }
+
+    fn start(
+        &self,
+        timeout: Duration,
+    ) -> Result<Box<dyn Controller>, BotError> {
+        match self {
+            Entrant::Autopilot => Ok(Box::new(Autopilot::new())),
+            Entrant::Stdio { command, .. } => {
+                Ok(Box::new(StdioBot::spawn(command, timeout)?))
+            }
+        }
+    }
+}
+
+impl Ending {
+    fn name(self) -> &'static str {
+        match self {
+            Ending::Died(cause) => cause.name(),
+            Ending::Cleared => "cleared",
+            Ending::Cutoff => "cutoff",
+            Ending::Disqualified => "disqualified",
+        }
+    }
+}
+
+impl Summary {
+    const ENDINGS: [Ending; 6] = [
+        Ending::Died(DeathCause::Edge),
+        Ending::Died(DeathCause::SelfCollision),
+        Ending::Died(DeathCause::StoneWall),
+        Ending::Cleared,
+        Ending::Cutoff,
+        Ending::Disqualified,
+    ];
+
+    fn new(name: &str, results: &[GameResult]) -> Self {
+        let games = results.len();
+        let mut scores: Vec<u32> = results.iter()
+            .map(|result| result.score)
+            .collect();
+        scores.sort_unstable();
+        let mean = |total: f64| total / games.max(1) as f64;
+        let total_score = scores.iter().map(|&score| score as f64).sum();
+        let total_ticks = results.iter().map(|result| result.ticks as f64).sum();
+        Self {
+            name: name.to_string(),
+            games,
+            mean_score: mean(total_score),
+            median_score: median(&scores),
+            max_score: scores.last().copied().unwrap_or(0),
+            mean_ticks: mean(total_ticks),
+            endings: Self::ENDINGS.iter()
+                .map(|ending| {
+                    results.iter()
+                        .filter(|result| result.ending == *ending)
+                        .count()
+                })
+                .collect(),
+        }
+    }
+
+    fn header() -> Vec<String> {
+        let mut header: Vec<String> = [
+            "bot", "games", "mean", "median", "max", "mean_ticks",
+        ].iter().map(|title| title.to_string()).collect();
+        header.extend(
+            Self::ENDINGS.iter().map(|ending| ending.name().to_string()),
+        );
+        header
+    }
+
+    fn row(&self) -> Vec<String> {
+        let mut row = vec![
+            self.name.clone(),
+            self.games.to_string(),
+            format!("{:.1}", self.mean_score),
+            format!("{:.1}", self.median_score),
+            self.max_score.to_string(),
+            format!("{:.0}", self.mean_ticks),
+        ];
+        row.extend(self.endings.iter().map(|count| count.to_string()));
+        row
+    }
+}
+
+/// The middle of `sorted`, or halfway between the two middle values when
+/// there is an even number of them. Zero when there are none.
+fn median(sorted: &[u32]) -> f64 {
+    let len = sorted.len();
+    match len {
+        0 => 0.0,
+        _ if len.is_multiple_of(2) => {
+            (sorted[len / 2 - 1] as f64 + sorted[len / 2] as f64) / 2.0
+        }
+        _ => sorted[len / 2] as f64,
+    }
+}
+
+/// Plays one game, starting the bot again if it failed the game before.
+fn play_game(
+    entrant: &Entrant,
+    controller: &mut Option<Box<dyn Controller>>,
+    sim: &mut Simulation,
+    cli: &Cli,
+) -> Ending {
+    let timeout = Duration::from_millis(cli.bot_timeout);
+    if controller.is_none() {
+        match entrant.start(timeout) {
+            Ok(started) => *controller = Some(started),
+            Err(err) => {
+                eprintln!("{}: {}", entrant.name(), err);
+                return Ending::Disqualified;
+            }
+        }
+    }
+    let playing = controller.as_mut().unwrap().as_mut();
+    let played = controller::play(sim, playing, cli.max_ticks);
+    for fault in playing.take_faults() {
+        eprintln!("{} seed {}: fault at {}", entrant.name(), sim.seed, fault);
+    }
+    if let Err(err) = played {
+        eprintln!("{} seed {}: {}", entrant.name(), sim.seed, err);
+        *controller = None;
+        return Ending::Disqualified;
+    }
+    match (sim.won, sim.death) {
+        (true, _) => Ending::Cleared,
+        (false, Some(cause)) => Ending::Died(cause),
+        (false, None) => Ending::Cutoff,
+    }
+}
+
+fn table(rows: &[Vec<String>]) -> String {
+    let columns = rows.first().map_or(0, |row| row.len());
+    let widths: Vec<usize> = (0..columns)
+        .map(|column| {
+            rows.iter().map(|row| row[column].len()).max().unwrap_or(0)
+        })
+        .collect();
+    let mut text = String::new();
+    for row in rows {
+        let cells: Vec<String> = row.iter()
+            .zip(&widths)
+            .enumerate()
+            .map(|(column, (cell, width))| match column {
+                0 => format!("{:<width$}", cell, width = width),
+                _ => format!("{:>width$}", cell, width = width),
+            })
+            .collect();
+        let _ = writeln!(text, "{}", cells.join("  ").trim_end());
+    }
+    text
+}
+
+fn csv(rows: &[Vec<String>]) -> String {
+    let mut text = String::new();
+    for row in rows {
+        let cells: Vec<String> = row.iter()
+            .map(|cell| {
+                if cell.contains([',', '"', '\n']) {
+                    format!("\"{}\"", cell.replace('"', "\"\""))
+                } else {
+                    cell.clone()
+                }
+            })
+            .collect();
+        let _ = writeln!(text, "{}", cells.join(","));
+    }
+    text
+}
+
+fn main() {
+    let cli = Cli::parse();
+    let config = match (&cli.config, &cli.level) {
+        (Some(path), _) => Config::load(path, cli.preset.as_deref())
+            .unwrap_or_else(|err| {
+                eprintln!("failed to load config {}: {}", path.display(), err);
+                std::process::exit(1);
+            }),
+        (None, Some(path)) => match Level::load(path) {
+            Ok(level) => level.config,
+            Err(err) => {
+                eprintln!("failed to load level {}: {}", path.display(), err);
+                std::process::exit(1);
+            }
+        },
+        (None, None) => Config::default(),
+    };
+    let entrants: Vec<Entrant> = if cli.bots.is_empty() {
+        vec![Entrant::Autopilot]
+    } else {
+        cli.bots.iter()
+            .map(|value| Entrant::parse(value))
+            .collect::<Result<_, _>>()
+            .unwrap_or_else(|err| {
+                eprintln!("invalid --bot: {}", err);
+                std::process::exit(2);
+            })
+    };
+
+    let mut rows = vec![Summary::header()];
+    for entrant in &entrants {
+        let mut controller = None;
+        let results: Vec<GameResult> = (0..cli.games)
+            .map(|game| {
+                let mut sim = Simulation::new(
+                    cli.seed.wrapping_add(game as u64),
+                    config.clone(),
+                );
+                let ending =
+                    play_game(entrant, &mut controller, &mut sim, &cli);
+                GameResult {
+                    score: sim.score,
+                    ticks: sim.tick,
+                    ending,
+                }
+            })
+            .collect();
+        rows.push(Summary::new(entrant.name(), &results).row());
+    }
+
+    print!("{}", table(&rows));
+    if let Some(path) = &cli.csv {
+        if let Err(err) = fs::write(path, csv(&rows)) {
+            eprintln!("failed to write {}: {}", path.display(), err);
+            std::process::exit(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result(score: u32, ticks: u64, ending: Ending) -> GameResult {
+        GameResult { score, ticks, ending }
+    }
+
+    #[test]
+    fn medians_of_odd_and_even_counts() {
+        assert_eq!(median(&[]), 0.0);
+        assert_eq!(median(&[4]), 4.0);
+        assert_eq!(median(&[1, 2, 9]), 2.0);
+        assert_eq!(median(&[1, 2, 3, 9]), 2.5);
+    }
+
+    #[test]
+    fn summary_adds_up_the_games() {
+        let results = [
+            result(10, 100, Ending::Died(DeathCause::Edge)),
+            result(2, 50, Ending::Died(DeathCause::Edge)),
+            result(30, 600, Ending::Cleared),
+            result(0, 10, Ending::Disqualified),
+        ];
+        let summary = Summary::new("bot", &results);
+        assert_eq!(summary.games, 4);
+        assert_eq!(summary.mean_score, 10.5);
+        assert_eq!(summary.median_score, 6.0);
+        assert_eq!(summary.max_score, 30);
+        assert_eq!(summary.mean_ticks, 190.0);
+        assert_eq!(summary.endings, [2, 0, 0, 1, 0, 1]);
+
+        let summary = Summary::new("bot", &results[..3]);
+        assert_eq!(summary.median_score, 10.0);
+        assert_eq!(summary.mean_ticks, 250.0);
+
+        let summary = Summary::new("nobody", &[]);
+        assert_eq!(summary.mean_score, 0.0);
+        assert_eq!(summary.max_score, 0);
+        assert_eq!(summary.endings, [0; 6]);
+    }
+
+    #[test]
+    fn csv_has_a_row_per_bot() {
+        let results = [
+            result(7, 123, Ending::Cutoff),
+            result(3, 77, Ending::Died(DeathCause::StoneWall)),
+        ];
+        let rows = vec![
+            Summary::header(),
+            Summary::new("greedy, \"v2\"", &results).row(),
+        ];
+        assert_eq!(csv(&rows), "\
+            bot,games,mean,median,max,mean_ticks,edge,self,wall,\
+            cleared,cutoff,disqualified\n\
+            \"greedy, \"\"v2\"\"\",2,5.0,5.0,7,100,0,0,1,0,1,0\n");
+    }
+}
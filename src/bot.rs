@@ -0,0 +1,272 @@
+//! Lets an external program steer the snake over its stdin and stdout.
+//!
+//! Before each move the game writes the state as one line of JSON:
+//!
+//! ```text
+//! {"seed":1,"tick":40,"width":76,"height":45,"wrap":false,
+//!  "snake":[[39,20],[38,20]],"direction":"RIGHT","apple":[10,4],
+//!  "blueberry":null,"golden_apple":null,"orange":[60,30],
+//!  "stone_walls":[[5,5]],"score":2,"shielded":false,"frame_delay":4.5}
+//! ```
+//!
+//! The game asks once per move rather than on every tick. Between moves
+//! nothing the bot is shown changes and no turn can take effect, so the
+//! ticks in between would only repeat the question.
+//!
+//! `snake` lists the squares from head to tail. When `wrap` is true the
+//! edges wrap around instead of killing the snake. The bot answers with one
+//! line: the `tick` it was sent, then `UP`, `DOWN`, `LEFT` or `RIGHT` to
+//! turn, or `NONE` to keep going, such as `40 UP`. Answers are not case
+//! sensitive, and turns the rules forbid are ignored. The first answer of
+//! each game must be a direction, since the snake does not move until it
+//! has one.
+//!
+//! A reply that is late or unreadable is a fault and counts as `NONE`. A
+//! late reply still arrives, but its tick no longer matches the question
+//! being asked, so it is dropped. Faults are kept for the caller to report
+//! through `Controller::take_faults`. After `MAX_FAULTS` faults in a row,
+//! or if the bot exits, it is disqualified and the run ends. One bot
+//! process plays every game of a session; a new `seed` with `tick` 0 marks
+//! the start of the next game.
+
+use crate::controller::Controller;
+use crate::simulation::{Direction, Simulation};
+
+use glam::IVec2;
+
+use serde::Serialize;
+
+use std::fmt;
+use std::io::{self, BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How many late or unreadable replies in a row disqualify a bot.
+pub const MAX_FAULTS: u32 = 3;
+
+pub struct StdioBot {
+    child: Child,
+    stdin: ChildStdin,
+    /// Lines read from the bot's stdout by a separate thread, so reading
+    /// can time out.
+    replies: Receiver<io::Result<String>>,
+    timeout: Duration,
+    /// Faults in a row.
+    faults: u32,
+    /// Faults not yet taken by the caller, each with its tick.
+    fault_log: Vec<String>,
+    /// The head position the bot was last asked about.
+    asked_at: Option<IVec2>,
+}
+
+#[derive(Debug)]
+pub enum BotError {
+    Spawn(io::Error),
+    Io(io::Error),
+    Exited,
+    Disqualified { last_fault: String },
+}
+
+impl fmt::Display for BotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BotError::Spawn(err) => write!(f, "failed to start bot: {}", err),
+            BotError::Io(err) => write!(f, "{}", err),
+            BotError::Exited => write!(f, "bot exited"),
+            BotError::Disqualified { last_fault } => write!(
+                f,
+                "bot disqualified after {} faults in a row (last: {})",
+                MAX_FAULTS, last_fault,
+            ),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct State {
+    seed: u64,
+    tick: u64,
+    width: i32,
+    height: i32,
+    wrap: bool,
+    snake: Vec<IVec2>,
+    direction: Option<&'static str>,
+    apple: IVec2,
+    blueberry: Option<IVec2>,
+    golden_apple: Option<IVec2>,
+    orange: Option<IVec2>,
+    stone_walls: Vec<IVec2>,
+    score: u32,
+    shielded: bool,
+    frame_delay: f32,
+}
+
+impl State {
+    fn new(sim: &Simulation) -> Self {
+        let mut stone_walls: Vec<IVec2> = sim.stone_walls.iter()
+            .copied()
+            .collect();
+        stone_walls.sort_by_key(|pos| (pos.y, pos.x));
+        Self {
+            seed: sim.seed,
+            tick: sim.tick,
+            width: sim.config.dimensions.x,
+            height: sim.config.dimensions.y,
+            wrap: sim.config.wrap,
+            snake: sim.snake.iter().rev().copied().collect(),
+            direction: sim.direction.map(Direction::name),
+            apple: sim.apple,
+            blueberry: sim.blueberry,
+            golden_apple: sim.golden_apple,
+            orange: sim.orange,
+            stone_walls,
+            score: sim.score,
+            shielded: sim.shielded,
+            frame_delay: sim.frame_data.frame_delay,
+        }
+    }
+}
+
+impl StdioBot {
+    /// Starts `command`, split into words like a shell would, as a bot that
+    /// gets `timeout` to answer each move.
+    pub fn spawn(command: &str, timeout: Duration) -> Result<Self, BotError> {
+        let words = shlex::split(command)
+            .filter(|words| !words.is_empty())
+            .ok_or_else(|| BotError::Spawn(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("invalid bot command '{}'", command),
+            )))?;
+        let mut child = Command::new(&words[0])
+            .args(&words[1..])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .map_err(BotError::Spawn)?;
+        let stdin = child.stdin.take().expect("stdin is piped");
+        let stdout = child.stdout.take().expect("stdout is piped");
+        let (sender, replies) = mpsc::channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+        Ok(Self {
+            child,
+            stdin,
+            replies,
+            timeout,
+            faults: 0,
+            fault_log: Vec::new(),
+            asked_at: None,
+        })
+    }
+
+    /// Sends the state and waits for the bot's answer to it.
+    fn ask(&mut self, sim: &Simulation) -> Result<Option<Direction>, BotError> {
+        let line = serde_json::to_string(&State::new(sim))
+            .expect("bot state is always serializable");
+        writeln!(self.stdin, "{}", line)
+            .and_then(|_| self.stdin.flush())
+            .map_err(|err| match err.kind() {
+                io::ErrorKind::BrokenPipe => BotError::Exited,
+                _ => BotError::Io(err),
+            })?;
+
+        let deadline = Instant::now() + self.timeout;
+        let fault = loop {
+            let left = deadline.saturating_duration_since(Instant::now());
+            let reply = match self.replies.recv_timeout(left) {
+                Ok(Ok(reply)) => reply,
+                Ok(Err(err)) => return Err(BotError::Io(err)),
+                Err(RecvTimeoutError::Disconnected) => {
+                    return Err(BotError::Exited);
+                }
+                Err(RecvTimeoutError::Timeout) => break format!(
+                    "no reply within {}ms",
+                    self.timeout.as_millis(),
+                ),
+            };
+            match parse_reply(&reply) {
+                // A late answer to an earlier question.
+                Some((tick, _)) if tick != sim.tick => continue,
+                Some((_, None)) if !sim.started() => {
+                    break "NONE before the first move".to_string();
+                }
+                Some((_, input)) => {
+                    self.faults = 0;
+                    return Ok(input);
+                }
+                None => {
+                    break format!("unreadable reply '{}'", reply.trim());
+                }
+            }
+        };
+        self.faults += 1;
+        if self.faults >= MAX_FAULTS {
+            return Err(BotError::Disqualified { last_fault: fault });
+        }
+        self.fault_log.push(format!("tick {}: {}", sim.tick, fault));
+        Ok(None)
+    }
+}
+
+/// Reads one reply line into the tick it answers and the move, where
+/// `None` means keep going.
+fn parse_reply(reply: &str) -> Option<(u64, Option<Direction>)> {
+    let reply = reply.trim().to_ascii_uppercase();
+    let (tick, answer) = reply.split_once(char::is_whitespace)?;
+    let tick = tick.parse().ok()?;
+    match answer.trim_start() {
+        "NONE" => Some((tick, None)),
+        answer => Direction::from_name(answer).map(|dir| (tick, Some(dir))),
+    }
+}
+
+impl Controller for StdioBot {
+    /// Asks once per move, after any queued turns have been used up.
+    fn next_input(
+        &mut self,
+        sim: &Simulation,
+    ) -> Result<Option<Direction>, BotError> {
+        if !sim.live || !sim.queued_turns.is_empty() {
+            return Ok(None);
+        }
+        let head = sim.snake.head();
+        if self.asked_at == Some(head) && sim.started() {
+            return Ok(None);
+        }
+        self.asked_at = Some(head);
+        self.ask(sim)
+    }
+
+    fn take_faults(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.fault_log)
+    }
+}
+
+impl Drop for StdioBot {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replies_carry_their_tick() {
+        assert_eq!(parse_reply("40 UP\n"), Some((40, Some(Direction::UP))));
+        assert_eq!(parse_reply(" 7   left "), Some((7, Some(Direction::LEFT))));
+        assert_eq!(parse_reply("0 none"), Some((0, None)));
+        assert_eq!(parse_reply("UP"), None);
+        assert_eq!(parse_reply("40 SIDEWAYS"), None);
+        assert_eq!(parse_reply("-1 UP"), None);
+    }
+}
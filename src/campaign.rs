@@ -0,0 +1,284 @@
+//! Levels played in order, each with a goal to meet. Clearing a level
+//! unlocks the next one, and the levels cleared are saved to the data
+//! directory.
+//!
+//! A campaign file lists level files, relative to itself:
+//!
+//! ```toml
+//! levels = ["field.toml", "pillars.toml"]
+//! ```
+
+use crate::level::{Level, LevelError};
+
+use serde::{Deserialize, Serialize};
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+pub const CAMPAIGN_PATH: &str = "levels/campaign.toml";
+const PROGRESS_FILE: &str = "campaign.toml";
+
+pub struct Campaign {
+    pub levels: Vec<Level>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CampaignFile {
+    levels: Vec<PathBuf>,
+}
+
+#[derive(Debug)]
+pub enum CampaignError {
+    Io(io::Error),
+    Parse(toml::de::Error),
+    Level { path: PathBuf, error: LevelError },
+    /// A level, by name, that has no goal to meet.
+    NoGoal(String),
+    /// Two levels with the same name, which progress could not tell apart.
+    DuplicateName(String),
+    Empty,
+}
+
+impl fmt::Display for CampaignError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CampaignError::Io(err) => write!(f, "{}", err),
+            CampaignError::Parse(err) => write!(f, "{}", err),
+            CampaignError::Level { path, error } => {
+                write!(f, "{}: {}", path.display(), error)
+            }
+            CampaignError::NoGoal(name) => {
+                write!(f, "level '{}' has no goal", name)
+            }
+            CampaignError::DuplicateName(name) => {
+                write!(f, "more than one level is named '{}'", name)
+            }
+            CampaignError::Empty => write!(f, "the campaign has no levels"),
+        }
+    }
+}
+
+/// The campaign levels cleared on this machine, by name.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Progress {
+    #[serde(default)]
+    pub cleared: Vec<String>,
+}
+
+#[derive(Debug)]
+pub enum ProgressError {
+    NoDataDir,
+    Io(io::Error),
+    Parse(toml::de::Error),
+}
+
+impl fmt::Display for ProgressError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProgressError::NoDataDir => write!(f, "no user data directory"),
+            ProgressError::Io(err) => write!(f, "{}", err),
+            ProgressError::Parse(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl From<io::Error> for ProgressError {
+    fn from(err: io::Error) -> Self {
+        ProgressError::Io(err)
+    }
+}
+
+impl Campaign {
+    /// Loads the campaign file and every level in it. Each level must be
+    /// playable and have a goal.
+    pub fn load(path: &Path) -> Result<Self, CampaignError> {
+        let text = fs::read_to_string(path).map_err(CampaignError::Io)?;
+        let file: CampaignFile = toml::from_str(&text)
+            .map_err(CampaignError::Parse)?;
+        if file.levels.is_empty() {
+            return Err(CampaignError::Empty);
+        }
+        let dir = path.parent().unwrap_or_else(|| Path::new(""));
+        let mut levels: Vec<Level> = Vec::new();
+        for level_path in file.levels {
+            let level_path = dir.join(level_path);
+            let level = Level::load(&level_path).map_err(|error| {
+                CampaignError::Level { path: level_path, error }
+            })?;
+            if level.config.goal.is_none() {
+                return Err(CampaignError::NoGoal(level.name));
+            }
+            if levels.iter().any(|other| other.name == level.name) {
+                return Err(CampaignError::DuplicateName(level.name));
+            }
+            levels.push(level);
+        }
+        Ok(Self { levels })
+    }
+
+    /// Whether level `index` can be played: it is the first, or the one
+    /// before it has been cleared.
+    pub fn is_unlocked(&self, progress: &Progress, index: usize) -> bool {
+        index == 0 || progress.is_cleared(&self.levels[index - 1].name)
+    }
+}
+
+impl Progress {
+    pub fn path() -> Result<PathBuf, ProgressError> {
+        let dir = dirs::data_dir().ok_or(ProgressError::NoDataDir)?;
+        Ok(dir.join("snake-game").join(PROGRESS_FILE))
+    }
+
+    /// Reads progress from the data directory. A missing file is no
+    /// progress.
+    pub fn load() -> Result<Self, ProgressError> {
+        let text = match fs::read_to_string(Self::path()?) {
+            Ok(text) => text,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                return Ok(Self::default());
+            }
+            Err(err) => return Err(err.into()),
+        };
+        toml::from_str(&text).map_err(ProgressError::Parse)
+    }
+
+    pub fn save(&self) -> Result<(), ProgressError> {
+        let path = Self::path()?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let text = toml::to_string(self)
+            .expect("progress is always serializable");
+        fs::write(path, text)?;
+        Ok(())
+    }
+
+    pub fn is_cleared(&self, name: &str) -> bool {
+        self.cleared.iter().any(|cleared| cleared == name)
+    }
+
+    /// Marks a level cleared. Returns whether it is newly cleared.
+    pub fn record(&mut self, name: &str) -> bool {
+        if self.is_cleared(name) {
+            return false;
+        }
+        self.cleared.push(name.to_string());
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A directory of its own for each test, removed when dropped.
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(test: &str) -> Self {
+            let dir = std::env::temp_dir().join(format!(
+                "snake-game-campaign-{}-{}",
+                test,
+                std::process::id(),
+            ));
+            fs::create_dir_all(&dir).unwrap();
+            Self(dir)
+        }
+
+        /// Writes a campaign of `levels`, each a file name with its name
+        /// and whether it has a goal, and returns the campaign file.
+        fn campaign(&self, levels: &[(&str, &str, bool)]) -> PathBuf {
+            let map = format!("S.......\n{}", "........\n".repeat(7));
+            for (file, name, goal) in levels {
+                let mut text = format!(
+                    "name = \"{}\"\nmap = \"\"\"\n{}\"\"\"\n",
+                    name,
+                    map,
+                );
+                if *goal {
+                    text += "goal = { kind = \"apples\", target = 5 }\n";
+                }
+                fs::write(self.0.join(file), text).unwrap();
+            }
+            let files: Vec<String> = levels.iter()
+                .map(|(file, _, _)| format!("\"{}\"", file))
+                .collect();
+            let path = self.0.join("campaign.toml");
+            fs::write(&path, format!("levels = [{}]\n", files.join(", ")))
+                .unwrap();
+            path
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn the_shipped_campaign_loads() {
+        let campaign = Campaign::load(Path::new(CAMPAIGN_PATH)).unwrap();
+        assert!(!campaign.levels.is_empty());
+    }
+
+    #[test]
+    fn levels_unlock_in_order() {
+        let dir = TempDir::new("unlock");
+        let path = dir.campaign(&[
+            ("one.toml", "One", true),
+            ("two.toml", "Two", true),
+            ("three.toml", "Three", true),
+        ]);
+        let campaign = Campaign::load(&path).unwrap();
+        let mut progress = Progress::default();
+        let unlocked = |progress: &Progress| -> Vec<bool> {
+            (0..3).map(|index| campaign.is_unlocked(progress, index))
+                .collect()
+        };
+        assert_eq!(unlocked(&progress), [true, false, false]);
+        assert!(progress.record("One"));
+        assert_eq!(unlocked(&progress), [true, true, false]);
+        // Clearing a later level does not unlock the ones between.
+        assert!(progress.record("Three"));
+        assert_eq!(unlocked(&progress), [true, true, false]);
+        assert!(progress.record("Two"));
+        assert_eq!(unlocked(&progress), [true, true, true]);
+    }
+
+    #[test]
+    fn clearing_a_level_again_is_not_new() {
+        let mut progress = Progress::default();
+        assert!(progress.record("One"));
+        assert!(!progress.record("One"));
+        assert_eq!(progress.cleared, ["One"]);
+    }
+
+    #[test]
+    fn bad_campaigns_are_rejected() {
+        let dir = TempDir::new("bad");
+        let path = dir.campaign(&[]);
+        assert!(matches!(Campaign::load(&path), Err(CampaignError::Empty)));
+
+        let path = dir.campaign(&[
+            ("one.toml", "One", true),
+            ("two.toml", "Two", false),
+        ]);
+        match Campaign::load(&path) {
+            Err(CampaignError::NoGoal(name)) => assert_eq!(name, "Two"),
+            other => panic!("expected no goal, got {:?}", other.err()),
+        }
+
+        let path = dir.campaign(&[
+            ("one.toml", "One", true),
+            ("two.toml", "One", true),
+        ]);
+        match Campaign::load(&path) {
+            Err(CampaignError::DuplicateName(name)) => assert_eq!(name, "One"),
+            other => panic!("expected a duplicate, got {:?}", other.err()),
+        }
+    }
+}
@@ -0,0 +1,118 @@
+use clap::{ArgGroup, Parser};
+
+use glam::IVec2;
+
+use std::path::PathBuf;
+
+/// Snake with seeded runs, replays and tunable rules.
+#[derive(Parser)]
+#[command(name = "snake-game", version)]
+#[command(group(
+    ArgGroup::new("player")
+        .args(["replay", "autopilot", "bot", "host", "join"])
+))]
+pub struct Cli {
+    /// Seed for every random placement. Without it each game picks a fresh
+    /// random seed.
+    #[arg(long, conflicts_with = "replay")]
+    pub seed: Option<u64>,
+
+    /// Board size in squares, overriding the config.
+    #[arg(
+        long,
+        value_name = "WIDTHxHEIGHT",
+        value_parser = parse_board_size,
+        conflicts_with = "replay",
+    )]
+    pub board_size: Option<IVec2>,
+
+    /// Gameplay config file.
+    #[arg(long, value_name = "PATH", conflicts_with = "replay")]
+    pub config: Option<PathBuf>,
+
+    /// Level file to play instead of the open board.
+    #[arg(
+        long,
+        value_name = "PATH",
+        conflicts_with_all = ["replay", "config", "board_size"],
+    )]
+    pub level: Option<PathBuf>,
+
+    /// Named preset from the config file.
+    #[arg(long, value_name = "NAME", requires = "config")]
+    pub preset: Option<String>,
+
+    /// Open the level editor on this file, which is created when first
+    /// saved.
+    #[arg(
+        long,
+        value_name = "PATH",
+        conflicts_with_all = ["replay", "headless"],
+    )]
+    pub edit: Option<PathBuf>,
+
+    /// Host an online versus round on this port and wait for another
+    /// player to join.
+    #[arg(
+        long,
+        value_name = "PORT",
+        conflicts_with_all = ["replay", "edit"],
+    )]
+    pub host: Option<u16>,
+
+    /// Play the hosted round with rollback instead of lockstep: each side
+    /// runs ahead on a guess at the other's input rather than waiting for
+    /// it, and runs again if the guess was wrong.
+    #[arg(long, requires = "host")]
+    pub rollback: bool,
+
+    /// Join an online versus round hosted at this address. The host's
+    /// seed, config and netcode are used.
+    #[arg(
+        long,
+        value_name = "HOST:PORT",
+        conflicts_with_all = ["seed", "config", "level", "board_size", "edit"],
+    )]
+    pub join: Option<String>,
+
+    /// Run in a window instead of fullscreen.
+    #[arg(long)]
+    pub windowed: bool,
+
+    /// Play back a recorded replay. The replay's own seed and config are
+    /// used.
+    #[arg(long, value_name = "PATH")]
+    pub replay: Option<PathBuf>,
+
+    /// Let the built-in bot play. Tab toggles it during a game.
+    #[arg(long)]
+    pub autopilot: bool,
+
+    /// An external program to play, over the line-based JSON protocol on
+    /// its stdin and stdout.
+    #[arg(long, value_name = "COMMAND", requires = "headless")]
+    pub bot: Option<String>,
+
+    /// How long the bot gets to answer each move before it is skipped.
+    #[arg(long, value_name = "MS", default_value_t = 100, requires = "bot")]
+    pub bot_timeout: u64,
+
+    /// How many games the autopilot or bot plays without a window. Each
+    /// game uses the next seed after the one before.
+    #[arg(long, default_value_t = 1, requires = "headless")]
+    pub games: u32,
+
+    /// Run without a window, printing the result to stdout. Needs a replay,
+    /// the autopilot or a bot to play. Online, a simple built-in player
+    /// steers.
+    #[arg(long, requires = "player", conflicts_with = "windowed")]
+    pub headless: bool,
+}
+
+fn parse_board_size(value: &str) -> Result<IVec2, String> {
+    let (x, y) = value.split_once('x')
+        .ok_or_else(|| format!("expected WIDTHxHEIGHT, got '{}'", value))?;
+    let parse = |side: &str| side.parse::<i32>()
+        .map_err(|_| format!("invalid board side '{}'", side));
+    Ok(IVec2::new(parse(x)?, parse(y)?))
+}
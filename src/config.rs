@@ -0,0 +1,669 @@
+use crate::simulation::Direction;
+use crate::versus;
+
+use glam::IVec2;
+
+use serde::{Deserialize, Serialize};
+
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// The largest `off_limits_range`. Free squares count what blocks them in
+/// a `u16`, and each snake segment within range of a square is a blocker,
+/// so the area has to stay small enough for two snakes' worth of segments,
+/// overlapping tails included, to fit in the count.
+pub const MAX_OFF_LIMITS_RANGE: i32 = 63;
+
+/// Gameplay constants. Any field missing from a config file keeps its
+/// default value.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub target_fps: u32,
+    pub starting_frame_delay: u8,
+    pub frame_delay_decay: f32,
+    pub frame_delay_inc: f32,
+    pub bb_gen_frames: u32,
+    pub ga_gen_frames: u32,
+    pub or_gen_frames: u32,
+    pub sw_gen_frames: u32,
+    pub dimensions: IVec2,
+    pub golden_apple_worth: u32,
+    pub wall_break_worth: u32,
+    pub off_limits_range: i32,
+    /// Whether the edges wrap around, so leaving one side of the board
+    /// comes back in on the other instead of ending the game.
+    pub wrap: bool,
+    /// Which of the timed spawns happen. The apple always does.
+    pub spawns: Vec<Spawn>,
+    /// Stone walls on the board from the start.
+    pub walls: Vec<IVec2>,
+    /// Where the snake starts. Defaults to the middle of the board.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub start: Option<IVec2>,
+    /// The way the snake sets off as soon as the game starts. Without it
+    /// the snake waits for the first turn.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub start_direction: Option<Direction>,
+    /// Fruit on the board from the start, in place of the first random one
+    /// of each kind. Last so that it is written after the plain values.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub fruits: BTreeMap<Fruit, IVec2>,
+    /// What the player has to do to win, besides filling the board.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub goal: Option<Goal>,
+}
+
+/// Something a level asks of the player, such as eating 20 apples.
+/// Meeting it ends the run as a win.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Goal {
+    pub kind: GoalKind,
+    pub target: u32,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GoalKind {
+    /// Eat apples.
+    Apples,
+    /// Grow long, counting growth still to come.
+    Length,
+    /// Break stone walls with the shield.
+    WallsBroken,
+    /// Score points.
+    Score,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Fruit {
+    Apple,
+    Blueberry,
+    GoldenApple,
+    Orange,
+}
+
+/// Something that appears on the board by itself on a timer.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Spawn {
+    Blueberry,
+    GoldenApple,
+    Orange,
+    StoneWall,
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(io::Error),
+    Parse(toml::de::Error),
+    UnknownPreset { name: String, available: Vec<String> },
+    Invalid { field: &'static str, message: String },
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            target_fps: 60,
+            starting_frame_delay: 5,
+            frame_delay_decay: 0.92,
+            frame_delay_inc: 1.4,
+            bb_gen_frames: 720,
+            ga_gen_frames: 1080,
+            or_gen_frames: 560,
+            sw_gen_frames: 360,
+            dimensions: glam::const_ivec2!([76, 45]),
+            golden_apple_worth: 10,
+            wall_break_worth: 3,
+            off_limits_range: 3,
+            wrap: false,
+            spawns: Spawn::ALL.to_vec(),
+            walls: Vec::new(),
+            start: None,
+            start_direction: None,
+            fruits: BTreeMap::new(),
+            goal: None,
+        }
+    }
+}
+
+impl Fruit {
+    pub const ALL: [Fruit; 4] = [
+        Fruit::Apple,
+        Fruit::Blueberry,
+        Fruit::GoldenApple,
+        Fruit::Orange,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Fruit::Apple => "apple",
+            Fruit::Blueberry => "blueberry",
+            Fruit::GoldenApple => "golden apple",
+            Fruit::Orange => "orange",
+        }
+    }
+}
+
+impl Goal {
+    /// What is being counted, for showing progress.
+    pub fn name(self) -> &'static str {
+        match self.kind {
+            GoalKind::Apples => "apples",
+            GoalKind::Length => "length",
+            GoalKind::WallsBroken => "walls broken",
+            GoalKind::Score => "score",
+        }
+    }
+
+    /// The goal as an instruction, such as "eat 20 apples".
+    pub fn describe(self) -> String {
+        let target = self.target;
+        match self.kind {
+            GoalKind::Apples => format!("eat {} apples", target),
+            GoalKind::Length => format!("reach length {}", target),
+            GoalKind::WallsBroken => {
+                format!("break {} walls with the shield", target)
+            }
+            GoalKind::Score => format!("score {}", target),
+        }
+    }
+}
+
+impl Spawn {
+    pub const ALL: [Spawn; 4] = [
+        Spawn::Blueberry,
+        Spawn::GoldenApple,
+        Spawn::Orange,
+        Spawn::StoneWall,
+    ];
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Io(err) => write!(f, "{}", err),
+            ConfigError::Parse(err) => write!(f, "{}", err),
+            ConfigError::UnknownPreset { name, available } => {
+                if available.is_empty() {
+                    write!(f, "unknown preset '{}' (no presets defined)", name)
+                } else {
+                    write!(
+                        f,
+                        "unknown preset '{}' (available: {})",
+                        name,
+                        available.join(", "),
+                    )
+                }
+            }
+            ConfigError::Invalid { field, message } => {
+                write!(f, "invalid {}: {}", field, message)
+            }
+        }
+    }
+}
+
+fn invalid(field: &'static str, message: String) -> ConfigError {
+    ConfigError::Invalid { field, message }
+}
+
+impl Config {
+    /// Loads a config file. Top-level keys override the defaults, and a
+    /// `[presets.<name>]` table, when `preset` names one, overrides those in
+    /// turn.
+    pub fn load(path: &Path, preset: Option<&str>) -> Result<Self, ConfigError> {
+        let text = fs::read_to_string(path).map_err(ConfigError::Io)?;
+        Self::from_toml(&text, preset)
+    }
+
+    pub fn from_toml(text: &str, preset: Option<&str>) -> Result<Self, ConfigError> {
+        let mut table: toml::value::Table = toml::from_str(text)
+            .map_err(ConfigError::Parse)?;
+        let presets = match table.remove("presets") {
+            Some(toml::Value::Table(presets)) => presets,
+            Some(_) => return Err(invalid(
+                "presets",
+                "expected a table of named presets".to_string(),
+            )),
+            None => toml::value::Table::new(),
+        };
+        if let Some(name) = preset {
+            match presets.get(name) {
+                Some(toml::Value::Table(overrides)) => {
+                    for (key, value) in overrides {
+                        table.insert(key.clone(), value.clone());
+                    }
+                }
+                Some(_) => return Err(invalid(
+                    "presets",
+                    format!("preset '{}' must be a table", name),
+                )),
+                None => return Err(ConfigError::UnknownPreset {
+                    name: name.to_string(),
+                    available: presets.keys().cloned().collect(),
+                }),
+            }
+        }
+        let config: Config = toml::Value::Table(table).try_into()
+            .map_err(ConfigError::Parse)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Rejects values the game cannot run with.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if self.target_fps == 0 {
+            return Err(invalid("target_fps", "must be positive".to_string()));
+        }
+        if self.starting_frame_delay == 0 {
+            return Err(invalid(
+                "starting_frame_delay",
+                "must be at least 1".to_string(),
+            ));
+        }
+        if !(self.frame_delay_decay > 0.0 && self.frame_delay_decay <= 1.0) {
+            return Err(invalid(
+                "frame_delay_decay",
+                format!("{} is not in (0, 1]", self.frame_delay_decay),
+            ));
+        }
+        if !(self.frame_delay_inc >= 0.0 && self.frame_delay_inc.is_finite()) {
+            return Err(invalid(
+                "frame_delay_inc",
+                format!("{} is not a non-negative number", self.frame_delay_inc),
+            ));
+        }
+        for (field, frames) in [
+            ("bb_gen_frames", self.bb_gen_frames),
+            ("ga_gen_frames", self.ga_gen_frames),
+            ("or_gen_frames", self.or_gen_frames),
+            ("sw_gen_frames", self.sw_gen_frames),
+        ] {
+            if frames == 0 {
+                return Err(invalid(field, "must be positive".to_string()));
+            }
+        }
+        if self.off_limits_range < 0 {
+            return Err(invalid(
+                "off_limits_range",
+                "must not be negative".to_string(),
+            ));
+        }
+        if self.off_limits_range > MAX_OFF_LIMITS_RANGE {
+            return Err(invalid(
+                "off_limits_range",
+                format!(
+                    "{} is above the largest allowed, {}",
+                    self.off_limits_range,
+                    MAX_OFF_LIMITS_RANGE,
+                ),
+            ));
+        }
+        let min_dim = 2 * self.off_limits_range + 2;
+        if self.dimensions.x < min_dim || self.dimensions.y < min_dim {
+            return Err(invalid(
+                "dimensions",
+                format!(
+                    "{}x{} is too small; both sides must be at least {} \
+                     for an off_limits_range of {}",
+                    self.dimensions.x,
+                    self.dimensions.y,
+                    min_dim,
+                    self.off_limits_range,
+                ),
+            ));
+        }
+        let walls: HashSet<IVec2> = self.walls.iter().copied().collect();
+        let off_board = self.walls.iter().find(|wall| !self.on_board(**wall));
+        if let Some(wall) = off_board {
+            return Err(invalid(
+                "walls",
+                format!("({}, {}) is off the board", wall.x, wall.y),
+            ));
+        }
+        let start = self.start_pos();
+        if !self.on_board(start) {
+            return Err(invalid(
+                "start",
+                format!("({}, {}) is off the board", start.x, start.y),
+            ));
+        }
+        if walls.contains(&start) {
+            return Err(invalid(
+                "start",
+                format!("({}, {}) is a wall", start.x, start.y),
+            ));
+        }
+        let mut taken = walls.clone();
+        taken.insert(start);
+        for (fruit, pos) in &self.fruits {
+            let problem = if !self.on_board(*pos) {
+                "is off the board"
+            } else if !taken.insert(*pos) {
+                "is on a wall, the start or another fruit"
+            } else {
+                continue;
+            };
+            return Err(invalid(
+                "fruits",
+                format!(
+                    "the {} at ({}, {}) {}",
+                    fruit.name(),
+                    pos.x,
+                    pos.y,
+                    problem,
+                ),
+            ));
+        }
+        if self.goal.is_some_and(|goal| goal.target == 0) {
+            return Err(invalid("goal", "target must be positive".to_string()));
+        }
+        // The first apple goes on a square clear of the start, the walls
+        // and the fixed fruit, unless it is placed itself.
+        let has_room = self.fruits.contains_key(&Fruit::Apple) ||
+            (0..self.dimensions.y)
+                .flat_map(|y| {
+                    (0..self.dimensions.x).map(move |x| IVec2::new(x, y))
+                })
+                .any(|pos| !taken.contains(&pos) && !self.near_start(pos));
+        if !has_room {
+            return Err(invalid(
+                "walls",
+                "leave no room for an apple, counting fixed fruit".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Checks a config for a versus round as well as for single player.
+    /// There the two snakes start at `versus::start_positions`, and the
+    /// first apple needs a square clear of both.
+    pub fn validate_versus(&self) -> Result<(), ConfigError> {
+        self.validate()?;
+        let starts = versus::start_positions(self.dimensions);
+        // Walls and fixed fruit on a start square are left out.
+        let mut taken: HashSet<IVec2> = self.walls.iter()
+            .chain(self.fruits.values())
+            .copied()
+            .collect();
+        for start in starts {
+            taken.remove(&start);
+        }
+        let placed_apple = self.fruits.get(&Fruit::Apple)
+            .is_some_and(|apple| !starts.contains(apple));
+        let has_room = placed_apple ||
+            (0..self.dimensions.y)
+                .flat_map(|y| {
+                    (0..self.dimensions.x).map(move |x| IVec2::new(x, y))
+                })
+                .any(|pos| {
+                    !taken.contains(&pos) &&
+                        starts.iter().all(|start| !self.near(*start, pos))
+                });
+        if !has_room {
+            return Err(invalid(
+                "walls",
+                "leave no room for an apple clear of both versus starts"
+                    .to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Where the snake starts.
+    pub fn start_pos(&self) -> IVec2 {
+        self.start.unwrap_or(self.dimensions / 2)
+    }
+
+    fn on_board(&self, pos: IVec2) -> bool {
+        pos.x >= 0 && pos.x < self.dimensions.x &&
+            pos.y >= 0 && pos.y < self.dimensions.y
+    }
+
+    /// Whether `pos` is in the off-limits area around the start, measured
+    /// across the edges when they wrap.
+    fn near_start(&self, pos: IVec2) -> bool {
+        self.near(self.start_pos(), pos)
+    }
+
+    /// Whether `pos` is in the off-limits area around `center`.
+    fn near(&self, center: IVec2, pos: IVec2) -> bool {
+        let mut gap = (pos - center).abs();
+        if self.wrap {
+            gap = gap.min(self.dimensions - gap);
+        }
+        gap.max_element() <= self.off_limits_range
+    }
+
+    pub fn to_toml(&self) -> String {
+        toml::to_string(self).expect("config is always serializable")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn invalid_field(config: &Config) -> Option<&'static str> {
+        match config.validate() {
+            Err(ConfigError::Invalid { field, .. }) => Some(field),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn missing_keys_keep_their_defaults() {
+        let config = Config::from_toml("target_fps = 30\nwrap = true\n", None)
+            .unwrap();
+        assert_eq!(
+            config,
+            Config {
+                target_fps: 30,
+                wrap: true,
+                ..Config::default()
+            },
+        );
+    }
+
+    #[test]
+    fn unknown_keys_are_rejected() {
+        let result = Config::from_toml("target_fsp = 30\n", None);
+        assert!(matches!(result, Err(ConfigError::Parse(_))));
+    }
+
+    #[test]
+    fn presets_override_the_top_level() {
+        let text = "target_fps = 30\n\
+                    [presets.fast]\n\
+                    target_fps = 90\n";
+        let config = Config::from_toml(text, Some("fast")).unwrap();
+        assert_eq!(config.target_fps, 90);
+        let config = Config::from_toml(text, None).unwrap();
+        assert_eq!(config.target_fps, 30);
+    }
+
+    #[test]
+    fn a_missing_preset_is_an_error() {
+        let text = "[presets.fast]\ntarget_fps = 90\n";
+        match Config::from_toml(text, Some("slow")) {
+            Err(ConfigError::UnknownPreset { name, available }) => {
+                assert_eq!(name, "slow");
+                assert_eq!(available, ["fast"]);
+            }
+            other => panic!("expected an unknown preset, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn a_placed_apple_needs_no_room() {
+        let walls: Vec<IVec2> = (0..8)
+            .flat_map(|y| (0..8).map(move |x| IVec2::new(x, y)))
+            .filter(|pos| (*pos - IVec2::new(4, 4)).abs().max_element() > 1)
+            .filter(|pos| *pos != IVec2::ZERO)
+            .collect();
+        let config = Config {
+            dimensions: IVec2::new(8, 8),
+            off_limits_range: 1,
+            walls,
+            fruits: BTreeMap::from([(Fruit::Apple, IVec2::ZERO)]),
+            ..Config::default()
+        };
+        assert!(config.validate().is_ok());
+        let sim = crate::simulation::Simulation::new(1, config);
+        assert_eq!(sim.apple, IVec2::ZERO);
+    }
+
+    #[test]
+    fn the_default_is_valid() {
+        assert!(Config::default().validate().is_ok());
+    }
+
+    #[test]
+    fn each_invalid_value_is_caught() {
+        let small = Config {
+            dimensions: IVec2::new(8, 8),
+            off_limits_range: 1,
+            ..Config::default()
+        };
+        // Walls on every square outside the area around the start.
+        let boxed_in = Config {
+            walls: (0..8)
+                .flat_map(|y| (0..8).map(move |x| IVec2::new(x, y)))
+                .filter(|pos| (*pos - IVec2::new(4, 4)).abs().max_element() > 1)
+                .collect(),
+            ..small.clone()
+        };
+        // The same but for one square, which a fixed orange takes.
+        let mut walls = boxed_in.walls.clone();
+        walls.retain(|pos| *pos != IVec2::ZERO);
+        let filled_by_fruit = Config {
+            walls,
+            fruits: BTreeMap::from([(Fruit::Orange, IVec2::ZERO)]),
+            ..small.clone()
+        };
+        let cases = [
+            ("target_fps", Config { target_fps: 0, ..small.clone() }),
+            (
+                "starting_frame_delay",
+                Config { starting_frame_delay: 0, ..small.clone() },
+            ),
+            (
+                "frame_delay_decay",
+                Config { frame_delay_decay: 0.0, ..small.clone() },
+            ),
+            (
+                "frame_delay_decay",
+                Config { frame_delay_decay: 1.5, ..small.clone() },
+            ),
+            (
+                "frame_delay_inc",
+                Config { frame_delay_inc: -1.0, ..small.clone() },
+            ),
+            (
+                "frame_delay_inc",
+                Config { frame_delay_inc: f32::NAN, ..small.clone() },
+            ),
+            ("bb_gen_frames", Config { bb_gen_frames: 0, ..small.clone() }),
+            ("ga_gen_frames", Config { ga_gen_frames: 0, ..small.clone() }),
+            ("or_gen_frames", Config { or_gen_frames: 0, ..small.clone() }),
+            ("sw_gen_frames", Config { sw_gen_frames: 0, ..small.clone() }),
+            (
+                "off_limits_range",
+                Config { off_limits_range: -1, ..small.clone() },
+            ),
+            (
+                "off_limits_range",
+                Config {
+                    dimensions: IVec2::new(1000, 1000),
+                    off_limits_range: MAX_OFF_LIMITS_RANGE + 1,
+                    ..small.clone()
+                },
+            ),
+            (
+                "dimensions",
+                Config { dimensions: IVec2::new(3, 8), ..small.clone() },
+            ),
+            (
+                "walls",
+                Config { walls: vec![IVec2::new(8, 0)], ..small.clone() },
+            ),
+            (
+                "start",
+                Config { start: Some(IVec2::new(-1, 0)), ..small.clone() },
+            ),
+            (
+                "start",
+                Config {
+                    walls: vec![IVec2::new(1, 1)],
+                    start: Some(IVec2::new(1, 1)),
+                    ..small.clone()
+                },
+            ),
+            (
+                "fruits",
+                Config {
+                    fruits: BTreeMap::from([(Fruit::Apple, IVec2::new(0, 9))]),
+                    ..small.clone()
+                },
+            ),
+            (
+                "fruits",
+                Config {
+                    walls: vec![IVec2::new(0, 0)],
+                    fruits: BTreeMap::from([(Fruit::Orange, IVec2::new(0, 0))]),
+                    ..small.clone()
+                },
+            ),
+            (
+                "goal",
+                Config {
+                    goal: Some(Goal { kind: GoalKind::Apples, target: 0 }),
+                    ..small.clone()
+                },
+            ),
+            ("walls", boxed_in),
+            ("walls", filled_by_fruit),
+        ];
+        assert!(small.validate().is_ok());
+        for (field, config) in &cases {
+            assert_eq!(invalid_field(config), Some(*field), "{:?}", config);
+        }
+    }
+
+    #[test]
+    fn versus_needs_room_clear_of_both_starts() {
+        let config = Config {
+            dimensions: IVec2::new(8, 8),
+            off_limits_range: 1,
+            ..Config::default()
+        };
+        assert!(config.validate_versus().is_ok());
+        // Walls on every square away from both starts, (2, 4) and (5, 4).
+        let starts = versus::start_positions(config.dimensions);
+        let walls: Vec<IVec2> = (0..8)
+            .flat_map(|y| (0..8).map(move |x| IVec2::new(x, y)))
+            .filter(|pos| {
+                starts.iter().all(|start| !config.near(*start, *pos))
+            })
+            .collect();
+        let boxed_in = Config { walls, ..config.clone() };
+        assert!(boxed_in.validate().is_ok());
+        match boxed_in.validate_versus() {
+            Err(ConfigError::Invalid { field: "walls", .. }) => (),
+            other => panic!("expected no room, got {:?}", other),
+        }
+        // A placed apple needs no room, unless it sits on a start.
+        let apple = |pos| Config {
+            fruits: BTreeMap::from([(Fruit::Apple, pos)]),
+            ..boxed_in.clone()
+        };
+        assert!(apple(IVec2::new(3, 4)).validate_versus().is_ok());
+        assert!(apple(starts[1]).validate_versus().is_err());
+    }
+}
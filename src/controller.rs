@@ -0,0 +1,43 @@
+use crate::autopilot::Autopilot;
+use crate::bot::BotError;
+use crate::simulation::{Direction, Simulation};
+
+/// Something other than a keyboard that steers the snake.
+pub trait Controller {
+    /// The turn to request before the next frame, if any. An error means
+    /// the controller can no longer play and the run should stop.
+    fn next_input(
+        &mut self,
+        sim: &Simulation,
+    ) -> Result<Option<Direction>, BotError>;
+
+    /// What went wrong but was played through since the last call, such as
+    /// a bot's late replies, oldest first. Reporting them is up to the
+    /// caller.
+    fn take_faults(&mut self) -> Vec<String> {
+        Vec::new()
+    }
+}
+
+impl Controller for Autopilot {
+    fn next_input(
+        &mut self,
+        sim: &Simulation,
+    ) -> Result<Option<Direction>, BotError> {
+        Ok(Autopilot::next_input(self, sim))
+    }
+}
+
+/// Lets `controller` play `sim` until the run ends or `max_ticks` frames
+/// have passed.
+pub fn play(
+    sim: &mut Simulation,
+    controller: &mut dyn Controller,
+    max_ticks: u64,
+) -> Result<(), BotError> {
+    while sim.live && sim.tick < max_ticks {
+        let input = controller.next_input(sim)?;
+        sim.step(input);
+    }
+    Ok(())
+}
@@ -0,0 +1,318 @@
+//! A gym-style environment for training agents, with no window or graphics
+//! dependency.
+//!
+//! ```
+//! use snake_game::config::Config;
+//! use snake_game::env::Env;
+//! use snake_game::simulation::Direction;
+//!
+//! let mut env = Env::new(Config::default());
+//! let _observation = env.reset(7);
+//! let mut score = 0.0;
+//! loop {
+//!     // A real agent would choose its action from the observation.
+//!     let (_observation, reward, done, _info) =
+//!         env.step(Some(Direction::UP));
+//!     score += reward;
+//!     if done {
+//!         break;
+//!     }
+//! }
+//! assert_eq!(score, env.info().score as f32);
+//! ```
+
+use crate::config::Config;
+use crate::simulation::{Direction, Simulation, StepOutcome};
+
+use glam::IVec2;
+
+/// One layer of the observation grid.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Channel {
+    Body,
+    Head,
+    Apple,
+    Blueberry,
+    GoldenApple,
+    Orange,
+    Wall,
+}
+
+impl Channel {
+    pub const ALL: [Channel; 7] = [
+        Channel::Body,
+        Channel::Head,
+        Channel::Apple,
+        Channel::Blueberry,
+        Channel::GoldenApple,
+        Channel::Orange,
+        Channel::Wall,
+    ];
+}
+
+/// What an agent sees: one 0/1 grid per channel, plus the state that is
+/// not tied to a square.
+#[derive(Clone, Debug)]
+pub struct Observation {
+    pub dimensions: IVec2,
+    /// The channels in `Channel::ALL` order, each stored row by row.
+    pub grid: Vec<f32>,
+    pub shielded: bool,
+    /// Frames between moves. Lower is faster.
+    pub frame_delay: f32,
+}
+
+/// Details of the run so far, for logging rather than learning.
+#[derive(Copy, Clone, Debug)]
+pub struct Info {
+    pub score: u32,
+    pub length: usize,
+    pub ticks: u64,
+    pub won: bool,
+}
+
+pub struct Env {
+    config: Config,
+    sim: Simulation,
+}
+
+impl Observation {
+    fn new(sim: &Simulation) -> Self {
+        let dimensions = sim.config.dimensions;
+        let area = dimensions.x as usize * dimensions.y as usize;
+        let mut observation = Self {
+            dimensions,
+            grid: vec![0.0; Channel::ALL.len() * area],
+            shielded: sim.shielded,
+            frame_delay: sim.frame_data.frame_delay,
+        };
+        for pos in sim.snake.iter() {
+            observation.set(Channel::Body, *pos);
+        }
+        observation.set(Channel::Head, sim.snake.head());
+        observation.set(Channel::Apple, sim.apple);
+        let fruits = [
+            (Channel::Blueberry, sim.blueberry),
+            (Channel::GoldenApple, sim.golden_apple),
+            (Channel::Orange, sim.orange),
+        ];
+        for (channel, fruit) in fruits {
+            if let Some(pos) = fruit {
+                observation.set(channel, pos);
+            }
+        }
+        for pos in &sim.stone_walls {
+            observation.set(Channel::Wall, *pos);
+        }
+        observation
+    }
+
+    fn index(&self, channel: Channel, pos: IVec2) -> usize {
+        let area = self.dimensions.x as usize * self.dimensions.y as usize;
+        channel as usize * area +
+            pos.y as usize * self.dimensions.x as usize +
+            pos.x as usize
+    }
+
+    fn set(&mut self, channel: Channel, pos: IVec2) {
+        let index = self.index(channel, pos);
+        self.grid[index] = 1.0;
+    }
+
+    /// The value of `channel` at `pos`, which must be on the board.
+    pub fn get(&self, channel: Channel, pos: IVec2) -> f32 {
+        self.grid[self.index(channel, pos)]
+    }
+}
+
+impl Env {
+    /// Creates an environment playing by `config`. Call `reset` to start
+    /// each episode.
+    pub fn new(config: Config) -> Self {
+        Self {
+            sim: Simulation::new(0, config.clone()),
+            config,
+        }
+    }
+
+    /// Starts a new episode on `seed`.
+    pub fn reset(&mut self, seed: u64) -> Observation {
+        self.sim = Simulation::new(seed, self.config.clone());
+        Observation::new(&self.sim)
+    }
+
+    /// Turns towards `action`, if given and allowed, then runs until the
+    /// snake has moved once. The reward is the score gained on the way.
+    /// Until the first direction is given the snake stays still.
+    pub fn step(
+        &mut self,
+        action: Option<Direction>,
+    ) -> (Observation, f32, bool, Info) {
+        let score = self.sim.score;
+        let mut outcome = self.sim.step(action);
+        while outcome == StepOutcome::Waiting {
+            outcome = self.sim.step(None);
+        }
+        let reward = (self.sim.score - score) as f32;
+        (Observation::new(&self.sim), reward, !self.sim.live, self.info())
+    }
+
+    pub fn info(&self) -> Info {
+        Info {
+            score: self.sim.score,
+            length: self.sim.snake.len(),
+            ticks: self.sim.tick,
+            won: self.sim.won,
+        }
+    }
+
+    /// The game being played, for agents that want more than the
+    /// observation.
+    pub fn simulation(&self) -> &Simulation {
+        &self.sim
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{Fruit, Goal, GoalKind};
+
+    use std::collections::BTreeMap;
+
+    /// A small board with nothing spawning by itself. The snake starts at
+    /// (2, 2) with a golden apple and an orange to its right, and a wall
+    /// and the apple below the orange.
+    fn config() -> Config {
+        Config {
+            dimensions: IVec2::new(10, 10),
+            starting_frame_delay: 2,
+            spawns: Vec::new(),
+            start: Some(IVec2::new(2, 2)),
+            walls: vec![IVec2::new(6, 5)],
+            fruits: BTreeMap::from([
+                (Fruit::Apple, IVec2::new(6, 7)),
+                (Fruit::Blueberry, IVec2::new(0, 9)),
+                (Fruit::GoldenApple, IVec2::new(4, 2)),
+                (Fruit::Orange, IVec2::new(6, 2)),
+            ]),
+            ..Config::default()
+        }
+    }
+
+    /// The squares set in `channel`.
+    fn squares(observation: &Observation, channel: Channel) -> Vec<IVec2> {
+        let dimensions = observation.dimensions;
+        (0..dimensions.y)
+            .flat_map(|y| (0..dimensions.x).map(move |x| IVec2::new(x, y)))
+            .filter(|pos| observation.get(channel, *pos) == 1.0)
+            .collect()
+    }
+
+    #[test]
+    fn reset_observes_the_board() {
+        let mut env = Env::new(config());
+        let observation = env.reset(1);
+        let expected = [
+            (Channel::Body, IVec2::new(2, 2)),
+            (Channel::Head, IVec2::new(2, 2)),
+            (Channel::Apple, IVec2::new(6, 7)),
+            (Channel::Blueberry, IVec2::new(0, 9)),
+            (Channel::GoldenApple, IVec2::new(4, 2)),
+            (Channel::Orange, IVec2::new(6, 2)),
+            (Channel::Wall, IVec2::new(6, 5)),
+        ];
+        for (channel, pos) in expected {
+            assert_eq!(squares(&observation, channel), [pos], "{:?}", channel);
+        }
+        assert!(!observation.shielded);
+        assert_eq!(observation.frame_delay, 2.0);
+    }
+
+    #[test]
+    fn rewards_are_the_score_gained() {
+        let mut env = Env::new(config());
+        env.reset(1);
+        let worth = env.config.golden_apple_worth as f32;
+        let wall_worth = env.config.wall_break_worth as f32;
+        let moves = [
+            (Direction::RIGHT, 0.0),
+            (Direction::RIGHT, worth),
+            (Direction::RIGHT, 0.0),
+            // The orange
+            (Direction::RIGHT, 1.0),
+            (Direction::DOWN, 0.0),
+            (Direction::DOWN, 0.0),
+            (Direction::DOWN, wall_worth),
+            (Direction::DOWN, 0.0),
+            (Direction::DOWN, 1.0),
+        ];
+        for (direction, expected) in moves {
+            let (observation, reward, done, info) = env.step(Some(direction));
+            assert_eq!(reward, expected, "at {:?}", env.sim.snake.head());
+            assert!(!done);
+            assert_eq!(info.score, env.sim.score);
+            if env.sim.snake.head() == IVec2::new(6, 2) {
+                assert!(observation.shielded);
+            }
+        }
+        assert_eq!(env.info().score as f32, worth + wall_worth + 2.0);
+        assert!(!env.sim.shielded);
+        let observation = Observation::new(&env.sim);
+        let body = squares(&observation, Channel::Body);
+        assert_eq!(body.len(), env.info().length);
+        assert!(body.contains(&IVec2::new(4, 2)));
+        assert!(squares(&observation, Channel::Wall).is_empty());
+    }
+
+    #[test]
+    fn death_ends_the_episode() {
+        let mut env = Env::new(config());
+        env.reset(1);
+        assert!(!env.step(Some(Direction::UP)).2);
+        assert!(!env.step(None).2);
+        let (observation, reward, done, info) = env.step(None);
+        assert!(done);
+        assert_eq!(reward, 0.0);
+        assert!(!info.won);
+        assert_eq!(info.length, 1);
+        assert_eq!(squares(&observation, Channel::Head), [IVec2::new(2, 0)]);
+    }
+
+    #[test]
+    fn meeting_the_goal_wins_the_episode() {
+        let config = Config {
+            fruits: BTreeMap::from([(Fruit::Apple, IVec2::new(4, 2))]),
+            goal: Some(Goal { kind: GoalKind::Apples, target: 1 }),
+            ..config()
+        };
+        let mut env = Env::new(config);
+        env.reset(1);
+        assert!(!env.step(Some(Direction::RIGHT)).2);
+        let (_, reward, done, info) = env.step(None);
+        assert!(done);
+        assert_eq!(reward, 1.0);
+        assert!(info.won);
+        assert_eq!(info.score, 1);
+    }
+
+    #[test]
+    fn the_same_seed_gives_the_same_observations() {
+        let config = Config {
+            dimensions: IVec2::new(20, 12),
+            ..Config::default()
+        };
+        let mut envs = [Env::new(config.clone()), Env::new(config)];
+        let [first, second] = envs.each_mut().map(|env| env.reset(9));
+        assert_eq!(first.grid, second.grid);
+        let actions = [Direction::UP, Direction::LEFT, Direction::DOWN];
+        for action in actions.into_iter().cycle().take(30) {
+            let [first, second] = envs.each_mut()
+                .map(|env| env.step(Some(action)));
+            assert_eq!(first.0.grid, second.0.grid);
+            assert_eq!(first.0.frame_delay, second.0.frame_delay);
+            assert_eq!(first.1, second.1);
+            assert_eq!(first.2, second.2);
+        }
+    }
+}
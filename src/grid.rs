@@ -0,0 +1,53 @@
+use glam::IVec2;
+
+use serde::{Deserialize, Serialize};
+
+/// One value per square of the board, stored row by row.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Grid<T> {
+    dimensions: IVec2,
+    cells: Vec<T>,
+}
+
+impl<T: Clone> Grid<T> {
+    pub fn new(dimensions: IVec2, value: T) -> Self {
+        Self {
+            dimensions,
+            cells: vec![value; dimensions.x as usize * dimensions.y as usize],
+        }
+    }
+}
+
+impl<T> Grid<T> {
+    pub fn dimensions(&self) -> IVec2 {
+        self.dimensions
+    }
+
+    pub fn contains(&self, pos: IVec2) -> bool {
+        pos.x >= 0 && pos.x < self.dimensions.x &&
+            pos.y >= 0 && pos.y < self.dimensions.y
+    }
+
+    fn index(&self, pos: IVec2) -> Option<usize> {
+        if self.contains(pos) {
+            Some(pos.y as usize * self.dimensions.x as usize + pos.x as usize)
+        } else {
+            None
+        }
+    }
+
+    /// The value at `pos`, or `None` off the board.
+    pub fn get(&self, pos: IVec2) -> Option<&T> {
+        self.index(pos).map(|i| &self.cells[i])
+    }
+
+    pub fn get_mut(&mut self, pos: IVec2) -> Option<&mut T> {
+        self.index(pos).map(move |i| &mut self.cells[i])
+    }
+}
+
+/// `pos` moved onto a board of `dimensions` by wrapping it around the edges,
+/// so leaving one side comes back in on the other.
+pub fn wrap(pos: IVec2, dimensions: IVec2) -> IVec2 {
+    IVec2::new(pos.x.rem_euclid(dimensions.x), pos.y.rem_euclid(dimensions.y))
+}
@@ -0,0 +1,525 @@
+//! Hand-made stages, stored as TOML with the board drawn as ASCII art:
+//!
+//! ```toml
+//! name = "Pillars"
+//! map = """
+//! ............
+//! ..#......#..
+//! .....>......
+//! ..#......#..
+//! ............
+//! """
+//! sw_gen_frames = 720
+//! spawns = ["blueberry", "orange"]
+//! goal = { kind = "length", target = 40 }
+//! ```
+//!
+//! In the map `.` is open and `#` is a stone wall. The snake starts on `S`,
+//! or on `^`, `v`, `<` or `>` to set off that way as soon as the level
+//! starts. `a`, `b`, `g` and `o` place an apple, blueberry, golden apple or
+//! orange from the start. Every other key is a gameplay config key, as in a
+//! config file, apart from the ones the map sets: `dimensions`, `walls`,
+//! `start`, `start_direction` and `fruits`. Presets belong to config files
+//! and are not used in levels.
+//!
+//! A level must be playable: every open square can be reached from the
+//! start, and there is room for fruit away from it.
+
+use crate::config::{Config, ConfigError, Fruit};
+use crate::grid::{self, Grid};
+use crate::simulation::Direction;
+
+use glam::IVec2;
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Config keys that come from the map and may not be set directly.
+const MAP_KEYS: [&str; 5] = [
+    "dimensions",
+    "walls",
+    "start",
+    "start_direction",
+    "fruits",
+];
+
+/// How many squares the snake must be able to reach, away from where it
+/// starts, for a level to be worth playing.
+pub const MIN_ROOM: usize = 16;
+
+pub struct Level {
+    pub name: String,
+    /// The config to play the level with, walls and start included.
+    pub config: Config,
+}
+
+#[derive(Debug)]
+pub enum LevelError {
+    Io(io::Error),
+    Parse(toml::de::Error),
+    /// A top-level key that is missing, has the wrong type or may not be
+    /// set.
+    Key { key: String, message: &'static str },
+    /// Something wrong with the map, on the given line of it counting from
+    /// one, if it is down to one line.
+    Map { line: Option<usize>, message: String },
+    Config(ConfigError),
+    /// The level loads but cannot be played properly.
+    Unplayable(String),
+}
+
+impl fmt::Display for LevelError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LevelError::Io(err) => write!(f, "{}", err),
+            LevelError::Parse(err) => write!(f, "{}", err),
+            LevelError::Key { key, message } => {
+                write!(f, "'{}' {}", key, message)
+            }
+            LevelError::Map { line: Some(line), message } => {
+                write!(f, "map line {}: {}", line, message)
+            }
+            LevelError::Map { line: None, message } => {
+                write!(f, "map: {}", message)
+            }
+            LevelError::Config(err) => write!(f, "{}", err),
+            LevelError::Unplayable(message) => {
+                write!(f, "not playable: {}", message)
+            }
+        }
+    }
+}
+
+/// Reads a start marker. `Some(None)` is a start that waits for the first
+/// turn, and `None` means `square` is not a start at all.
+fn start_marker(square: char) -> Option<Option<Direction>> {
+    match square {
+        'S' => Some(None),
+        '^' => Some(Some(Direction::UP)),
+        'v' => Some(Some(Direction::DOWN)),
+        '<' => Some(Some(Direction::LEFT)),
+        '>' => Some(Some(Direction::RIGHT)),
+        _ => None,
+    }
+}
+
+fn start_symbol(direction: Option<Direction>) -> char {
+    match direction {
+        None => 'S',
+        Some(Direction::UP) => '^',
+        Some(Direction::DOWN) => 'v',
+        Some(Direction::LEFT) => '<',
+        Some(Direction::RIGHT) => '>',
+    }
+}
+
+fn fruit_symbol(fruit: Fruit) -> char {
+    match fruit {
+        Fruit::Apple => 'a',
+        Fruit::Blueberry => 'b',
+        Fruit::GoldenApple => 'g',
+        Fruit::Orange => 'o',
+    }
+}
+
+fn fruit_at(square: char) -> Option<Fruit> {
+    Fruit::ALL.into_iter().find(|fruit| fruit_symbol(*fruit) == square)
+}
+
+/// Reads the map into the config's dimensions, walls, start and fruit.
+fn read_map(map: &str, config: &mut Config) -> Result<(), LevelError> {
+    let map_error = |line: usize, message: String| LevelError::Map {
+        line: Some(line + 1),
+        message,
+    };
+    let rows: Vec<&str> = map.lines().map(str::trim).collect();
+    let rows = match rows.iter().position(|row| !row.is_empty()) {
+        Some(first) => {
+            let last = rows.iter().rposition(|row| !row.is_empty()).unwrap();
+            &rows[first..=last]
+        }
+        None => return Err(LevelError::Map {
+            line: None,
+            message: "the map is empty".to_string(),
+        }),
+    };
+    let width = rows[0].chars().count();
+    let mut walls = Vec::new();
+    let mut fruits = BTreeMap::new();
+    let mut start = None;
+    for (y, row) in rows.iter().enumerate() {
+        if row.chars().count() != width {
+            return Err(map_error(y, format!(
+                "{} squares wide where the first line is {}",
+                row.chars().count(),
+                width,
+            )));
+        }
+        for (x, square) in row.chars().enumerate() {
+            let pos = IVec2::new(x as i32, y as i32);
+            match (square, start_marker(square), fruit_at(square)) {
+                ('.', _, _) => (),
+                ('#', _, _) => walls.push(pos),
+                (_, Some(_), _) if start.is_some() => {
+                    return Err(map_error(y, "a second start".to_string()));
+                }
+                (_, Some(direction), _) => start = Some((pos, direction)),
+                (_, None, Some(fruit)) => {
+                    if fruits.insert(fruit, pos).is_some() {
+                        return Err(map_error(
+                            y,
+                            format!("a second {}", fruit.name()),
+                        ));
+                    }
+                }
+                (_, None, None) => {
+                    return Err(map_error(
+                        y,
+                        format!("unknown square '{}'", square),
+                    ));
+                }
+            }
+        }
+    }
+    let (start, start_direction) = start.ok_or_else(|| LevelError::Map {
+        line: None,
+        message: "no start, marked S, ^, v, < or >".to_string(),
+    })?;
+    config.dimensions = IVec2::new(width as i32, rows.len() as i32);
+    config.walls = walls;
+    config.start = Some(start);
+    config.start_direction = start_direction;
+    config.fruits = fruits;
+    Ok(())
+}
+
+impl Level {
+    pub fn load(path: &Path) -> Result<Self, LevelError> {
+        let text = fs::read_to_string(path).map_err(LevelError::Io)?;
+        Self::from_toml(&text)
+    }
+
+    /// Loads a level that may not be playable yet, for editing.
+    pub fn load_draft(path: &Path) -> Result<Self, LevelError> {
+        let text = fs::read_to_string(path).map_err(LevelError::Io)?;
+        Self::draft_from_toml(&text)
+    }
+
+    pub fn from_toml(text: &str) -> Result<Self, LevelError> {
+        let level = Self::draft_from_toml(text)?;
+        level.check()?;
+        Ok(level)
+    }
+
+    fn draft_from_toml(text: &str) -> Result<Self, LevelError> {
+        let mut table: toml::value::Table = toml::from_str(text)
+            .map_err(LevelError::Parse)?;
+        let mut take_string = |key: &str| {
+            let message = match table.remove(key) {
+                Some(toml::Value::String(value)) => return Ok(value),
+                Some(_) => "must be a string",
+                None => "is missing",
+            };
+            Err(LevelError::Key { key: key.to_string(), message })
+        };
+        let name = take_string("name")?;
+        let map = take_string("map")?;
+        let map_key = MAP_KEYS.iter().find(|key| table.contains_key(**key));
+        if let Some(key) = map_key {
+            return Err(LevelError::Key {
+                key: key.to_string(),
+                message: "is set by the map",
+            });
+        }
+        if table.contains_key("presets") {
+            return Err(LevelError::Key {
+                key: "presets".to_string(),
+                message: "only works in config files, not levels",
+            });
+        }
+        let mut config: Config = toml::Value::Table(table).try_into()
+            .map_err(LevelError::Parse)?;
+        read_map(&map, &mut config)?;
+        Ok(Self { name, config })
+    }
+
+    /// Checks that the config is valid and the level can be played: every
+    /// open square is reachable from the start, so no fruit can appear out
+    /// of reach, and at least `MIN_ROOM` of them are clear of the start.
+    pub fn check(&self) -> Result<(), LevelError> {
+        let config = &self.config;
+        config.validate().map_err(LevelError::Config)?;
+        let unplayable = |message: String| Err(LevelError::Unplayable(message));
+        let neighbour = |pos: IVec2, direction: Direction| {
+            let next = pos + direction.delta();
+            if config.wrap {
+                grid::wrap(next, config.dimensions)
+            } else {
+                next
+            }
+        };
+
+        let mut open = Grid::new(config.dimensions, true);
+        for wall in &config.walls {
+            *open.get_mut(*wall).unwrap() = false;
+        }
+        let start = config.start_pos();
+        if let Some(direction) = config.start_direction {
+            if open.get(neighbour(start, direction)) != Some(&true) {
+                return unplayable(format!(
+                    "the snake sets off {} into a wall",
+                    direction.name().to_lowercase(),
+                ));
+            }
+        }
+
+        // Flood fill from the start, closing each square as it is reached.
+        *open.get_mut(start).unwrap() = false;
+        let mut stack = vec![start];
+        let mut room = 0;
+        while let Some(pos) = stack.pop() {
+            let mut gap = (pos - start).abs();
+            if config.wrap {
+                gap = gap.min(config.dimensions - gap);
+            }
+            if gap.max_element() > config.off_limits_range {
+                room += 1;
+            }
+            for direction in Direction::ALL {
+                let next = neighbour(pos, direction);
+                if open.get(next) == Some(&true) {
+                    *open.get_mut(next).unwrap() = false;
+                    stack.push(next);
+                }
+            }
+        }
+        let dimensions = config.dimensions;
+        let cut_off = (0..dimensions.y)
+            .flat_map(|y| (0..dimensions.x).map(move |x| IVec2::new(x, y)))
+            .find(|pos| open.get(*pos) == Some(&true));
+        if let Some(pos) = cut_off {
+            return unplayable(format!(
+                "({}, {}) is open but cannot be reached from the start",
+                pos.x,
+                pos.y,
+            ));
+        }
+        if room < MIN_ROOM {
+            return unplayable(format!(
+                "only {} open squares are clear of the start, where at least \
+                 {} are needed",
+                room,
+                MIN_ROOM,
+            ));
+        }
+        Ok(())
+    }
+
+    /// The board as it is drawn in a level file.
+    pub fn map(&self) -> String {
+        let config = &self.config;
+        let dimensions = config.dimensions;
+        let mut squares = Grid::new(dimensions, '.');
+        for wall in &config.walls {
+            *squares.get_mut(*wall).unwrap() = '#';
+        }
+        for (fruit, pos) in &config.fruits {
+            *squares.get_mut(*pos).unwrap() = fruit_symbol(*fruit);
+        }
+        *squares.get_mut(config.start_pos()).unwrap() =
+            start_symbol(config.start_direction);
+        let mut map = String::new();
+        for y in 0..dimensions.y {
+            for x in 0..dimensions.x {
+                map.push(*squares.get(IVec2::new(x, y)).unwrap());
+            }
+            map.push('\n');
+        }
+        map
+    }
+
+    /// The level in the level file format. Only config keys that differ
+    /// from the defaults are written.
+    pub fn to_toml(&self) -> String {
+        let to_table = |config: &Config| match toml::Value::try_from(config) {
+            Ok(toml::Value::Table(table)) => table,
+            _ => unreachable!("config is always serializable to a table"),
+        };
+        let defaults = to_table(&Config::default());
+        let overrides: toml::value::Table = to_table(&self.config)
+            .into_iter()
+            .filter(|(key, value)| {
+                !MAP_KEYS.contains(&key.as_str()) &&
+                    defaults.get(key) != Some(value)
+            })
+            .collect();
+
+        let mut text = String::new();
+        let _ = writeln!(
+            text,
+            "name = {}",
+            toml::Value::String(self.name.clone()),
+        );
+        let _ = writeln!(text, "map = \"\"\"\n{}\"\"\"", self.map());
+        // As a value, so that tables such as the goal are written last.
+        text += &toml::to_string(&toml::Value::Table(overrides))
+            .expect("config overrides are always serializable");
+        text
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), LevelError> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(LevelError::Io)?;
+        }
+        fs::write(path, self.to_toml()).map_err(LevelError::Io)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{Goal, GoalKind, Spawn};
+
+    /// A level file with `map` and nothing else set.
+    fn level_text(map: &str) -> String {
+        format!("name = \"Test\"\nmap = \"\"\"\n{}\"\"\"\n", map)
+    }
+
+    fn map_error(map: &str) -> (Option<usize>, String) {
+        match read_map(map, &mut Config::default()) {
+            Err(LevelError::Map { line, message }) => (line, message),
+            other => panic!("expected a map error, got {:?}", other.err()),
+        }
+    }
+
+    fn unplayable(map: &str) -> String {
+        match Level::from_toml(&level_text(map)) {
+            Err(LevelError::Unplayable(message)) => message,
+            other => panic!("expected unplayable, got {:?}", other.err()),
+        }
+    }
+
+    #[test]
+    fn a_map_sets_the_board() {
+        let mut config = Config::default();
+        read_map("\n  #.a.\n  .v.o\n\n", &mut config).unwrap();
+        assert_eq!(config.dimensions, IVec2::new(4, 2));
+        assert_eq!(config.walls, [IVec2::new(0, 0)]);
+        assert_eq!(config.start, Some(IVec2::new(1, 1)));
+        assert_eq!(config.start_direction, Some(Direction::DOWN));
+        assert_eq!(config.fruits, BTreeMap::from([
+            (Fruit::Apple, IVec2::new(2, 0)),
+            (Fruit::Orange, IVec2::new(3, 1)),
+        ]));
+    }
+
+    #[test]
+    fn bad_maps_are_rejected() {
+        let (line, message) = map_error("S...\n...\n");
+        assert_eq!(line, Some(2));
+        assert!(message.contains("3 squares wide"), "{}", message);
+        assert_eq!(map_error("..\nSx\n"), (
+            Some(2),
+            "unknown square 'x'".to_string(),
+        ));
+        let (line, message) = map_error("....\n....\n");
+        assert_eq!(line, None);
+        assert!(message.starts_with("no start"), "{}", message);
+        assert_eq!(map_error("S..\n..>\n"), (
+            Some(2),
+            "a second start".to_string(),
+        ));
+        assert_eq!(map_error("S.a\na..\n"), (
+            Some(2),
+            "a second apple".to_string(),
+        ));
+        assert_eq!(map_error("\n\n").0, None);
+    }
+
+    #[test]
+    fn a_walled_in_start_is_unplayable() {
+        let map = "\
+            ###.....
+            #S#.....
+            ###.....
+            ........
+            ........
+            ........
+            ........
+            ........
+        ";
+        let message = unplayable(map);
+        assert!(message.contains("cannot be reached"), "{}", message);
+        let message = unplayable(&map.replace('S', ">"));
+        assert!(message.contains("into a wall"), "{}", message);
+    }
+
+    #[test]
+    fn too_little_room_is_unplayable() {
+        // Only the top row and left column are open, and just 8 of their
+        // squares are clear of the start.
+        let map = "\
+            S.......
+            .#######
+            .#######
+            .#######
+            .#######
+            .#######
+            .#######
+            .#######
+        ";
+        let message = unplayable(map);
+        let expected = format!("only 8 open squares are clear of the start, \
+            where at least {} are needed", MIN_ROOM);
+        assert_eq!(message, expected);
+    }
+
+    #[test]
+    fn keys_the_map_sets_are_rejected() {
+        let map = format!("S.......\n{}", "........\n".repeat(7));
+        for (key, value) in [("start", "[1, 1]"), ("presets", "{}")] {
+            let text = format!("{}{} = {}\n", level_text(&map), key, value);
+            match Level::from_toml(&text) {
+                Err(LevelError::Key { key: rejected, .. }) => {
+                    assert_eq!(rejected, key);
+                }
+                other => panic!("expected a key error, got {:?}", other.err()),
+            }
+        }
+        assert!(Level::from_toml(&level_text(&map)).is_ok());
+    }
+
+    #[test]
+    fn saved_levels_load_the_same() {
+        let mut level = Level::from_toml(&level_text("\
+            ..........
+            .#......o.
+            ...<......
+            .a......#.
+            ....g.....
+            .........b
+            ..........
+            ..........
+        ")).unwrap();
+        level.config.wrap = true;
+        level.config.spawns = vec![Spawn::Orange, Spawn::StoneWall];
+        level.config.golden_apple_worth = 5;
+        level.config.goal = Some(Goal { kind: GoalKind::Score, target: 30 });
+        level.check().unwrap();
+
+        let path = std::env::temp_dir()
+            .join(format!("snake-game-level-{}", std::process::id()))
+            .join("level.toml");
+        level.save(&path).unwrap();
+        let loaded = Level::load(&path);
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+        let loaded = loaded.unwrap();
+        assert_eq!(loaded.name, level.name);
+        assert_eq!(loaded.config, level.config);
+        assert_eq!(loaded.to_toml(), level.to_toml());
+    }
+}
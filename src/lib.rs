@@ -0,0 +1,23 @@
+//! The game rules and everything that works without a window: config,
+//! levels and the campaign, replays, the score table, the autopilot,
+//! external bots, a training environment and two-player versus, locally
+//! or over the network.
+//!
+//! Building with `--no-default-features` leaves out the windowed game, and
+//! with it ggez and the graphics and sound libraries it needs.
+
+pub mod autopilot;
+pub mod bot;
+pub mod campaign;
+pub mod config;
+pub mod controller;
+pub mod env;
+pub mod grid;
+pub mod level;
+pub mod lockstep;
+pub mod netplay;
+pub mod replay;
+pub mod rollback;
+pub mod scores;
+pub mod simulation;
+pub mod versus;
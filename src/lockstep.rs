@@ -0,0 +1,307 @@
+//! Versus over the network in deterministic lockstep.
+//!
+//! Both sides run the same `Versus` from the same seed and apply the same
+//! inputs on the same ticks, so they stay in step without sending any
+//! state. An input is sent `INPUT_DELAY` ticks ahead of the tick it applies
+//! to, which hides the round trip, and a tick only runs once the other
+//! side's input for it has arrived. Each input also carries a checksum of
+//! the sender's state when it was sent, which the receiver compares with
+//! its own state at that tick to catch a desync.
+
+use crate::netplay::{
+    is_hang_up, read_in_background, send_message, Message, NetError,
+};
+use crate::simulation::{Direction, StepOutcome};
+use crate::versus::{Versus, PLAYERS};
+
+use serde::{Deserialize, Serialize};
+
+use std::collections::VecDeque;
+use std::io::BufReader;
+use std::net::TcpStream;
+use std::sync::mpsc::{Receiver, TryRecvError};
+
+/// How many ticks ahead of time inputs are sent.
+pub const INPUT_DELAY: u64 = 3;
+
+/// What each side sends the other once per tick.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Input {
+    /// The tick the input applies to.
+    pub tick: u64,
+    pub direction: Option<Direction>,
+    /// The sender's checksum at `tick - INPUT_DELAY`.
+    pub checksum: u64,
+}
+
+/// Carries inputs between the two sides. Every input arrives, in the
+/// order it was sent.
+pub trait Link {
+    fn send(&mut self, input: &Input) -> Result<(), NetError>;
+
+    /// The next input to have arrived, if there is one. Never blocks.
+    fn receive(&mut self) -> Result<Option<Input>, NetError>;
+}
+
+/// One side of a versus round played over the network.
+pub struct Lockstep<L> {
+    pub versus: Versus,
+    /// The player this side steers. The host is the first player.
+    pub local: usize,
+    link: L,
+    /// How many ticks have run.
+    tick: u64,
+    /// Inputs sent but not yet applied, oldest first.
+    local_inputs: VecDeque<Option<Direction>>,
+    /// Inputs from the other side not yet applied, oldest first.
+    remote_inputs: VecDeque<Input>,
+    /// Checksums of the ticks the other side has yet to confirm, oldest
+    /// first, each with its tick.
+    checksums: VecDeque<(u64, u64)>,
+    /// Whether the other side has hung up.
+    hung_up: bool,
+}
+
+impl<L: Link> Lockstep<L> {
+    pub fn new(versus: Versus, local: usize, link: L) -> Self {
+        Self {
+            versus,
+            local,
+            link,
+            tick: 0,
+            local_inputs: (0..INPUT_DELAY).map(|_| None).collect(),
+            remote_inputs: VecDeque::new(),
+            checksums: VecDeque::new(),
+            hung_up: false,
+        }
+    }
+
+    /// Runs the next tick once the other side's input for it is in, and
+    /// sends `input` for `INPUT_DELAY` ticks from now. Never blocks.
+    /// Returns `None` if the other side's input has not arrived yet, and
+    /// the input should be offered again.
+    pub fn advance(
+        &mut self,
+        input: Option<Direction>,
+    ) -> Result<Option<StepOutcome>, NetError> {
+        self.receive()?;
+        let tick = self.tick;
+        let remote_input = if tick < INPUT_DELAY {
+            None
+        } else {
+            match self.remote_inputs.pop_front() {
+                Some(remote) => self.check(tick, remote)?,
+                // The other side can finish the round up to `INPUT_DELAY`
+                // ticks sooner and hang up. Its last inputs are in by
+                // then, so a hang-up only counts once they run out.
+                None if self.hung_up => return Err(NetError::Disconnected),
+                None => return Ok(None),
+            }
+        };
+
+        let checksum = self.versus.checksum();
+        self.checksums.push_back((tick, checksum));
+        let sent = self.link.send(&Input {
+            tick: tick + INPUT_DELAY,
+            direction: input,
+            checksum,
+        });
+        match sent {
+            Err(err) if is_hang_up(&err) => (),
+            sent => sent?,
+        }
+        self.local_inputs.push_back(input);
+
+        let local_input = self.local_inputs.pop_front()
+            .expect("inputs are queued ahead");
+        let mut inputs = [None; PLAYERS];
+        inputs[self.local] = local_input;
+        inputs[1 - self.local] = remote_input;
+        self.tick += 1;
+        Ok(Some(self.versus.step(inputs)))
+    }
+
+    /// Takes in every input that has arrived.
+    fn receive(&mut self) -> Result<(), NetError> {
+        while !self.hung_up {
+            match self.link.receive() {
+                Ok(Some(input)) => self.remote_inputs.push_back(input),
+                Ok(None) => break,
+                Err(NetError::Disconnected) => self.hung_up = true,
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks that `input` is the other side's input for `tick` and that
+    /// its checksum matches this side's.
+    fn check(
+        &mut self,
+        tick: u64,
+        input: Input,
+    ) -> Result<Option<Direction>, NetError> {
+        if input.tick != tick {
+            return Err(NetError::Protocol(format!(
+                "expected the input for tick {}, got {:?}",
+                tick,
+                input,
+            )));
+        }
+        let checked = tick - INPUT_DELAY;
+        while let Some(&(old, ours)) = self.checksums.front() {
+            if old > checked {
+                break;
+            }
+            self.checksums.pop_front();
+            if old == checked && ours != input.checksum {
+                return Err(NetError::Desync { tick: checked });
+            }
+        }
+        Ok(input.direction)
+    }
+}
+
+/// Inputs as lines on a TCP connection.
+pub struct TcpLink {
+    writer: TcpStream,
+    /// Inputs read by a separate thread, so receiving never blocks.
+    inputs: Receiver<Result<Input, NetError>>,
+}
+
+impl TcpLink {
+    /// Takes over a connection. `reader` reads from `stream`, and may
+    /// already hold what was sent after the hello.
+    pub(crate) fn new(
+        reader: BufReader<TcpStream>,
+        stream: TcpStream,
+    ) -> Self {
+        let inputs = read_in_background(reader, |message| match message {
+            Message::Input(input) => Ok(input),
+            message => Err(NetError::Protocol(format!(
+                "expected an input, got {:?}",
+                message,
+            ))),
+        });
+        Self {
+            writer: stream,
+            inputs,
+        }
+    }
+}
+
+impl Link for TcpLink {
+    fn send(&mut self, input: &Input) -> Result<(), NetError> {
+        send_message(&mut self.writer, &Message::Input(input.clone()))
+    }
+
+    fn receive(&mut self) -> Result<Option<Input>, NetError> {
+        match self.inputs.try_recv() {
+            Ok(input) => input.map(Some),
+            Err(TryRecvError::Empty) => Ok(None),
+            Err(TryRecvError::Disconnected) => Err(NetError::Disconnected),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    use glam::IVec2;
+
+    use std::sync::mpsc::{self, Sender};
+
+    /// Gives up on a round that has not ended by this many frames.
+    const MAX_FRAMES: u64 = 100_000;
+
+    /// One end of a pair of channels within the test.
+    struct ChannelLink {
+        outbox: Sender<Input>,
+        inbox: Receiver<Input>,
+    }
+
+    impl Link for ChannelLink {
+        fn send(&mut self, input: &Input) -> Result<(), NetError> {
+            self.outbox.send(input.clone())
+                .map_err(|_| NetError::Disconnected)
+        }
+
+        fn receive(&mut self) -> Result<Option<Input>, NetError> {
+            match self.inbox.try_recv() {
+                Ok(input) => Ok(Some(input)),
+                Err(TryRecvError::Empty) => Ok(None),
+                Err(TryRecvError::Disconnected) => Err(NetError::Disconnected),
+            }
+        }
+    }
+
+    fn config() -> Config {
+        Config {
+            dimensions: IVec2::new(20, 12),
+            ..Config::default()
+        }
+    }
+
+    /// Two sides of a round linked in-process, with the first as host.
+    fn sides(seed: u64) -> [Lockstep<ChannelLink>; PLAYERS] {
+        let (to_guest, from_host) = mpsc::channel();
+        let (to_host, from_guest) = mpsc::channel();
+        let host = ChannelLink { outbox: to_guest, inbox: from_guest };
+        let guest = ChannelLink { outbox: to_host, inbox: from_host };
+        [
+            Lockstep::new(Versus::new(seed, config()), 0, host),
+            Lockstep::new(Versus::new(seed, config()), 1, guest),
+        ]
+    }
+
+    #[test]
+    fn both_sides_agree_tick_for_tick() {
+        for seed in 0..5 {
+            let mut sides = sides(seed);
+            let mut checksums: [Vec<u64>; PLAYERS] = Default::default();
+            let mut waited = false;
+            for frame in 0.. {
+                if sides.iter().all(|side| side.versus.result.is_some()) {
+                    break;
+                }
+                assert!(frame < MAX_FRAMES, "round never ended");
+                for (index, side) in sides.iter_mut().enumerate() {
+                    // The guest only gets every other frame, so the host
+                    // keeps running ahead and having to wait for it.
+                    let skipped = index == 1 && frame % 2 == 1;
+                    if skipped || side.versus.result.is_some() {
+                        continue;
+                    }
+                    let input = side.versus.greedy_direction(side.local);
+                    match side.advance(input).unwrap() {
+                        Some(_) => {
+                            checksums[index].push(side.versus.checksum());
+                        }
+                        None => waited = true,
+                    }
+                }
+            }
+            assert!(waited);
+            assert_eq!(checksums[0], checksums[1]);
+            assert_eq!(sides[0].versus.result, sides[1].versus.result);
+        }
+    }
+
+    #[test]
+    fn a_checksum_mismatch_is_a_desync() {
+        let mut sides = sides(3);
+        sides[1].versus.players[0].score += 1;
+        let error = (0..MAX_FRAMES).find_map(|_| {
+            sides.iter_mut().find_map(|side| {
+                let input = side.versus.greedy_direction(side.local);
+                side.advance(input).err()
+            })
+        });
+        match error {
+            Some(NetError::Desync { tick: 0 }) => (),
+            other => panic!("expected a desync at tick 0, got {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,270 @@
+//! Versus over TCP against a player on another machine.
+//!
+//! The host listens and the guest connects. The host sends the seed, the
+//! config and which netcode the round is played with, and from then on
+//! each side sends only its own inputs. Every message is one line of JSON:
+//!
+//! ```text
+//! {"type":"hello","version":2,"seed":42,"netcode":"lockstep","config":"..."}
+//! {"type":"input","tick":40,"direction":"UP","checksum":1234567890}
+//! {"type":"packet","start":38,"inputs":[null,"UP"],"ack":37,...}
+//! ```
+//!
+//! `input` messages belong to `lockstep` and `packet` messages to
+//! `rollback`, which describe how each keeps both sides in step.
+
+use crate::config::{Config, ConfigError};
+use crate::lockstep::{Input, Lockstep, TcpLink};
+use crate::rollback::{Packet, Rollback, TcpTransport};
+use crate::simulation::{Direction, StepOutcome};
+use crate::versus::{Versus, VersusResult};
+
+use serde::{Deserialize, Serialize};
+
+use std::fmt;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::Duration;
+
+pub const PROTOCOL_VERSION: u32 = 2;
+/// How long to wait for the other side before giving up on it.
+pub const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How the two sides are kept in step.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Netcode {
+    /// Each tick waits for the other side's input.
+    Lockstep,
+    /// Ticks run on a guess at the other side's input and are run again
+    /// if the guess was wrong.
+    Rollback,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub(crate) enum Message {
+    Hello {
+        version: u32,
+        seed: u64,
+        netcode: Netcode,
+        /// The config as TOML, as in a config file.
+        config: String,
+    },
+    Input(Input),
+    Packet(Packet),
+}
+
+#[derive(Debug)]
+pub enum NetError {
+    Io(io::Error),
+    /// The other side sent something that does not follow the protocol.
+    Protocol(String),
+    /// The host's config did not load.
+    Config(ConfigError),
+    Disconnected,
+    /// The two sides' rounds differ as of `tick`.
+    Desync { tick: u64 },
+}
+
+impl fmt::Display for NetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NetError::Io(err) => write!(f, "{}", err),
+            NetError::Protocol(message) => {
+                write!(f, "protocol error: {}", message)
+            }
+            NetError::Config(err) => write!(f, "host config: {}", err),
+            NetError::Disconnected => write!(f, "the other player left"),
+            NetError::Desync { tick } => {
+                write!(f, "out of sync with the other player at tick {}", tick)
+            }
+        }
+    }
+}
+
+impl From<io::Error> for NetError {
+    fn from(err: io::Error) -> Self {
+        match err.kind() {
+            io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut => {
+                NetError::Disconnected
+            }
+            _ => NetError::Io(err),
+        }
+    }
+}
+
+/// One side of a versus round played over the network, with either
+/// netcode.
+pub enum Session {
+    Lockstep(Box<Lockstep<TcpLink>>),
+    Rollback(Box<Rollback<TcpTransport>>),
+}
+
+/// Waits on `port` for a guest, then starts a round with them.
+pub fn host(
+    port: u16,
+    seed: u64,
+    config: Config,
+    netcode: Netcode,
+) -> Result<Session, NetError> {
+    config.validate_versus().map_err(NetError::Config)?;
+    let listener = TcpListener::bind(("0.0.0.0", port))?;
+    let (mut stream, _) = listener.accept()?;
+    let reader = BufReader::new(stream.try_clone()?);
+    let hello = Message::Hello {
+        version: PROTOCOL_VERSION,
+        seed,
+        netcode,
+        config: config.to_toml(),
+    };
+    send_message(&mut stream, &hello)?;
+    Session::new(reader, stream, Versus::new(seed, config), 0, netcode)
+}
+
+/// Connects to a host at `addr` and starts the round it sends.
+pub fn join(addr: impl ToSocketAddrs) -> Result<Session, NetError> {
+    let stream = TcpStream::connect(addr)?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    let (seed, netcode, config) = match read_message(&mut reader)? {
+        Message::Hello { version, seed, netcode, config } => {
+            if version != PROTOCOL_VERSION {
+                return Err(NetError::Protocol(format!(
+                    "host speaks version {}, this is version {}",
+                    version,
+                    PROTOCOL_VERSION,
+                )));
+            }
+            (seed, netcode, config)
+        }
+        message => return Err(NetError::Protocol(format!(
+            "expected hello, got {:?}",
+            message,
+        ))),
+    };
+    let config = Config::from_toml(&config, None)
+        .and_then(|config| config.validate_versus().map(|()| config))
+        .map_err(NetError::Config)?;
+    Session::new(reader, stream, Versus::new(seed, config), 1, netcode)
+}
+
+impl Session {
+    /// Takes over a connection. `reader` reads from `stream`, and may
+    /// already hold what was sent after the hello.
+    fn new(
+        reader: BufReader<TcpStream>,
+        stream: TcpStream,
+        versus: Versus,
+        local: usize,
+        netcode: Netcode,
+    ) -> Result<Self, NetError> {
+        stream.set_nodelay(true)?;
+        stream.set_read_timeout(Some(TIMEOUT))?;
+        Ok(match netcode {
+            Netcode::Lockstep => {
+                let link = TcpLink::new(reader, stream);
+                Session::Lockstep(Box::new(Lockstep::new(versus, local, link)))
+            }
+            Netcode::Rollback => {
+                let transport = TcpTransport::new(reader, stream);
+                Session::Rollback(Box::new(
+                    Rollback::new(versus, local, transport),
+                ))
+            }
+        })
+    }
+
+    /// The round as this side shows it. Under rollback the latest ticks
+    /// may be a prediction.
+    pub fn versus(&self) -> &Versus {
+        match self {
+            Session::Lockstep(lockstep) => &lockstep.versus,
+            Session::Rollback(rollback) => &rollback.versus,
+        }
+    }
+
+    /// The player this side steers. The host is the first player.
+    pub fn local(&self) -> usize {
+        match self {
+            Session::Lockstep(lockstep) => lockstep.local,
+            Session::Rollback(rollback) => rollback.local,
+        }
+    }
+
+    /// Runs the next tick with the local player's input. Returns `None` if
+    /// the tick could not run yet, and the input should be offered again.
+    pub fn advance(
+        &mut self,
+        input: Option<Direction>,
+    ) -> Result<Option<StepOutcome>, NetError> {
+        match self {
+            Session::Lockstep(lockstep) => lockstep.advance(input),
+            Session::Rollback(rollback) => rollback.advance(input),
+        }
+    }
+
+    /// How the round ended, once both sides agree on it.
+    pub fn result(&self) -> Option<VersusResult> {
+        match self {
+            Session::Lockstep(lockstep) => lockstep.versus.result,
+            Session::Rollback(rollback) => rollback.result(),
+        }
+    }
+}
+
+pub(crate) fn send_message(
+    writer: &mut impl Write,
+    message: &Message,
+) -> Result<(), NetError> {
+    let mut line = serde_json::to_string(message)
+        .expect("messages are always serializable");
+    line.push('\n');
+    writer.write_all(line.as_bytes())?;
+    Ok(())
+}
+
+pub(crate) fn read_message(
+    reader: &mut impl BufRead,
+) -> Result<Message, NetError> {
+    let mut line = String::new();
+    if reader.read_line(&mut line)? == 0 {
+        return Err(NetError::Disconnected);
+    }
+    serde_json::from_str(&line)
+        .map_err(|err| NetError::Protocol(err.to_string()))
+}
+
+/// Reads messages on a separate thread, so they can be taken as they
+/// arrive without blocking. `expect` turns each message into what the
+/// netcode sends, or rejects it. The thread stops after the first error,
+/// which it passes on.
+pub(crate) fn read_in_background<T: Send + 'static>(
+    mut reader: BufReader<TcpStream>,
+    expect: fn(Message) -> Result<T, NetError>,
+) -> Receiver<Result<T, NetError>> {
+    let (sender, received) = mpsc::channel();
+    thread::spawn(move || loop {
+        let item = read_message(&mut reader).and_then(expect);
+        let failed = item.is_err();
+        if sender.send(item).is_err() || failed {
+            break;
+        }
+    });
+    received
+}
+
+/// Whether a send failed because the other side has hung up.
+pub(crate) fn is_hang_up(err: &NetError) -> bool {
+    match err {
+        NetError::Io(err) => matches!(
+            err.kind(),
+            io::ErrorKind::BrokenPipe |
+                io::ErrorKind::ConnectionReset |
+                io::ErrorKind::ConnectionAborted
+        ),
+        _ => false,
+    }
+}
@@ -0,0 +1,445 @@
+use crate::config::Fruit;
+use crate::simulation::{Direction, Simulation, Snake};
+use crate::versus::Versus;
+
+use ggez::{Context, GameResult};
+use ggez::graphics::{self, Color, Mesh, Text};
+
+use glam::{IVec2, Vec2};
+
+use std::collections::HashSet;
+
+const SCORE_STRIP: i32 = 4;
+pub const CIRCLE_TOLERANCE: f32 = 2.0;
+
+/// Where the board sits on screen: the side length of one square and the
+/// board's top-left corner, in pixels.
+pub struct GeoConfig {
+    pub dim: f32,
+    pub top_left: Vec2,
+}
+
+impl GeoConfig {
+    /// Fits a board of `dimensions` squares, plus the score strip below it,
+    /// into the current window, letterboxed to keep the squares square.
+    pub fn new(ctx: &Context, dimensions: IVec2) -> Self {
+        let (width, height) = graphics::drawable_size(ctx);
+        let total_dim_y = dimensions.y as f32 + SCORE_STRIP as f32;
+        let dim = (width / dimensions.x as f32).min(height / total_dim_y);
+        let used = Vec2::new(dim * dimensions.x as f32, dim * total_dim_y);
+        Self {
+            dim,
+            top_left: (Vec2::new(width, height) - used) / 2.0,
+        }
+    }
+}
+
+/// Queues `text` centred on `center`.
+pub fn queue_centered(
+    ctx: &mut Context,
+    text: &str,
+    center: Vec2,
+    size: f32,
+    color: Color,
+) {
+    let mut text = Text::new(text);
+    text.set_font(graphics::Font::default(), graphics::PxScale::from(size));
+    let bounds = text.dimensions(ctx);
+    graphics::queue_text(
+        ctx,
+        &text,
+        center - Vec2::new(bounds.w, bounds.h) / 2.0,
+        Some(color),
+    );
+}
+
+/// The board drawn as a single mesh. Building it is the expensive part, so
+/// it is kept until the board changes or the window is resized.
+#[derive(Default)]
+pub struct BoardMesh {
+    mesh: Option<Mesh>,
+}
+
+impl BoardMesh {
+    /// Forces the next draw to rebuild the mesh.
+    pub fn invalidate(&mut self) {
+        self.mesh = None;
+    }
+
+    /// Draws the play area, everything on it and the score strip.
+    pub fn draw(
+        &mut self,
+        ctx: &mut Context,
+        geo: &GeoConfig,
+        sim: &Simulation,
+    ) -> GameResult<()> {
+        if self.mesh.is_none() {
+            self.mesh = Some(build_board(ctx, geo, sim)?);
+        }
+        if let Some(mesh) = &self.mesh {
+            graphics::draw(ctx, mesh, graphics::DrawParam::default())?;
+        }
+
+        // Draw score
+        let dim = geo.dim;
+        let text_size = dim * 2.0;
+        graphics::queue_text(
+            ctx,
+            Text::new(sim.score.to_string()).set_font(
+                graphics::Font::default(),
+                graphics::PxScale::from(text_size),
+            ),
+            Vec2::new(
+                geo.top_left.x + text_size / 2.0,
+                geo.top_left.y
+                    + dim * sim.config.dimensions.y as f32
+                    + text_size / 2.0,
+            ),
+            Some(Color::WHITE),
+        );
+        Ok(())
+    }
+
+    /// Draws a versus board: both snakes, in their players' colours, and
+    /// each player's score at their own end of the score strip.
+    pub fn draw_versus(
+        &mut self,
+        ctx: &mut Context,
+        geo: &GeoConfig,
+        versus: &Versus,
+    ) -> GameResult<()> {
+        if self.mesh.is_none() {
+            self.mesh = Some(build_versus_board(ctx, geo, versus)?);
+        }
+        if let Some(mesh) = &self.mesh {
+            graphics::draw(ctx, mesh, graphics::DrawParam::default())?;
+        }
+
+        let dim = geo.dim;
+        let text_size = dim * 2.0;
+        let dimensions = versus.config.dimensions;
+        let y = geo.top_left.y + dim * dimensions.y as f32 + text_size / 2.0;
+        for (index, player) in versus.players.iter().enumerate() {
+            let mut text = Text::new(player.score.to_string());
+            text.set_font(
+                graphics::Font::default(),
+                graphics::PxScale::from(text_size),
+            );
+            let x = if index == 0 {
+                geo.top_left.x + text_size / 2.0
+            } else {
+                geo.top_left.x + dim * dimensions.x as f32
+                    - text.dimensions(ctx).w
+                    - text_size / 2.0
+            };
+            graphics::queue_text(
+                ctx,
+                &text,
+                Vec2::new(x, y),
+                Some(player_color(index)),
+            );
+        }
+        Ok(())
+    }
+}
+
+/// The colour of each versus player's snake. The first player's matches
+/// the single-player snake.
+pub fn player_color(index: usize) -> Color {
+    match index {
+        0 => Color::GREEN,
+        _ => Color::from_rgb_u32(0xc77dff),
+    }
+}
+
+/// Queues a short status line at the right end of the score strip.
+pub fn queue_status(
+    ctx: &mut Context,
+    geo: &GeoConfig,
+    dimensions: IVec2,
+    status: &str,
+) {
+    let dim = geo.dim;
+    let mut text = Text::new(status);
+    text.set_font(graphics::Font::default(), graphics::PxScale::from(dim));
+    let width = text.dimensions(ctx).w;
+    graphics::queue_text(
+        ctx,
+        &text,
+        Vec2::new(
+            geo.top_left.x + dim * dimensions.x as f32 - width - dim,
+            geo.top_left.y + dim * dimensions.y as f32 + dim * 1.5,
+        ),
+        Some(Color::from_rgb_u32(0xbfbfbf)),
+    );
+}
+
+pub fn fruit_color(fruit: Fruit) -> Color {
+    match fruit {
+        Fruit::Apple => Color::RED,
+        Fruit::Blueberry => Color::from_rgb_u32(0x4287f5),
+        Fruit::GoldenApple => Color::YELLOW,
+        Fruit::Orange => Color::from_rgb_u32(0xff9900),
+    }
+}
+
+/// The corners of the diamond drawn for a snake segment at `px_pos`.
+pub fn diamond(px_pos: Vec2, dim: f32) -> [Vec2; 4] {
+    let radius = dim / 2.0;
+    [
+        px_pos + Vec2::new(radius, 0.0),
+        px_pos + Vec2::new(dim, radius),
+        px_pos + Vec2::new(radius, dim),
+        px_pos + Vec2::new(0.0, radius),
+    ]
+}
+
+/// A bar `width` thick along the `side` edge of the square at `px_pos`.
+fn edge_bar(
+    px_pos: Vec2,
+    dim: f32,
+    side: Direction,
+    width: f32,
+) -> graphics::Rect {
+    let far = dim - width;
+    match side {
+        Direction::UP => graphics::Rect::new(px_pos.x, px_pos.y, dim, width),
+        Direction::DOWN => {
+            graphics::Rect::new(px_pos.x, px_pos.y + far, dim, width)
+        }
+        Direction::LEFT => graphics::Rect::new(px_pos.x, px_pos.y, width, dim),
+        Direction::RIGHT => {
+            graphics::Rect::new(px_pos.x + far, px_pos.y, width, dim)
+        }
+    }
+}
+
+/// Dashes the edges of a board with wrapping edges, to show they are open.
+fn build_wrap_edges(
+    builder: &mut graphics::MeshBuilder,
+    geo: &GeoConfig,
+    dimensions: IVec2,
+) -> GameResult<()> {
+    let dim = geo.dim;
+    let fill = graphics::DrawMode::Fill(graphics::FillOptions::default());
+    let last = dimensions - IVec2::ONE;
+    let mut dashes = Vec::new();
+    for x in (0..dimensions.x).step_by(2) {
+        dashes.push((IVec2::new(x, 0), Direction::UP));
+        dashes.push((IVec2::new(x, last.y), Direction::DOWN));
+    }
+    for y in (0..dimensions.y).step_by(2) {
+        dashes.push((IVec2::new(0, y), Direction::LEFT));
+        dashes.push((IVec2::new(last.x, y), Direction::RIGHT));
+    }
+    for (pos, side) in dashes {
+        let px_pos = pos.as_vec2() * dim + geo.top_left;
+        builder.rectangle(
+            fill,
+            edge_bar(px_pos, dim, side, dim / 8.0),
+            Color::from_rgb_u32(0x404040),
+        )?;
+    }
+    Ok(())
+}
+
+/// Lights up both ends of wherever `snake` crosses a wrapping edge.
+fn build_wrap_crossings(
+    builder: &mut graphics::MeshBuilder,
+    geo: &GeoConfig,
+    snake: &Snake,
+    color: Color,
+) -> GameResult<()> {
+    let dim = geo.dim;
+    let fill = graphics::DrawMode::Fill(graphics::FillOptions::default());
+    // Neighbouring segments more than a square apart cross an edge.
+    for (&from, &to) in snake.iter().zip(snake.iter().skip(1)) {
+        let gap = to - from;
+        if gap.abs().max_element() <= 1 {
+            continue;
+        }
+        let side = Direction::ALL.into_iter()
+            .find(|direction| direction.delta() == -gap.signum())
+            .expect("the snake only crosses one edge at a time");
+        for (pos, side) in [(from, side), (to, side.opposite())] {
+            let px_pos = pos.as_vec2() * dim + geo.top_left;
+            builder.rectangle(
+                fill,
+                edge_bar(px_pos, dim, side, dim / 4.0),
+                color,
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// Adds the play area, dashing its edges if they wrap.
+fn build_play_area(
+    builder: &mut graphics::MeshBuilder,
+    geo: &GeoConfig,
+    dimensions: IVec2,
+    wrap: bool,
+) -> GameResult<()> {
+    let dim = geo.dim;
+    let fill = graphics::DrawMode::Fill(graphics::FillOptions::default());
+    builder.rectangle(
+        fill,
+        graphics::Rect::new(
+            geo.top_left.x,
+            geo.top_left.y,
+            dim * dimensions.x as f32,
+            dim * dimensions.y as f32,
+        ),
+        Color::BLACK,
+    )?;
+    if wrap {
+        build_wrap_edges(builder, geo, dimensions)?;
+    }
+    Ok(())
+}
+
+/// Adds a snake, with its edge crossings on a wrapping board and a ring
+/// around its head while it is shielded.
+fn build_snake(
+    builder: &mut graphics::MeshBuilder,
+    geo: &GeoConfig,
+    snake: &Snake,
+    shielded: bool,
+    wrap: bool,
+    color: Color,
+) -> GameResult<()> {
+    let dim = geo.dim;
+    let fill = graphics::DrawMode::Fill(graphics::FillOptions::default());
+    if wrap {
+        build_wrap_crossings(builder, geo, snake, color)?;
+    }
+    for pos in snake.iter() {
+        let px_pos = pos.as_vec2() * dim + geo.top_left;
+        builder.polygon(fill, &diamond(px_pos, dim), color)?;
+    }
+    if shielded {
+        let px_pos = snake.head().as_vec2() * dim + geo.top_left;
+        builder.polygon(
+            graphics::DrawMode::Stroke(graphics::StrokeOptions::default()
+                .with_line_width(dim / 4.0)
+            ),
+            &diamond(px_pos, dim),
+            Color::from_rgb_u32(0xbfbfbf),
+        )?;
+    }
+    Ok(())
+}
+
+/// Adds the stone walls and whichever fruit is on the board.
+fn build_items(
+    builder: &mut graphics::MeshBuilder,
+    geo: &GeoConfig,
+    stone_walls: &HashSet<IVec2>,
+    fruit: [(Fruit, Option<IVec2>); 4],
+) -> GameResult<()> {
+    let dim = geo.dim;
+    let top_left = geo.top_left;
+    let radius = dim / 2.0;
+    let fill = graphics::DrawMode::Fill(graphics::FillOptions::default());
+
+    // Stone walls
+    for pos in stone_walls {
+        let px_pos = pos.as_vec2() * dim + top_left;
+        builder.rectangle(
+            fill,
+            graphics::Rect::new(px_pos.x, px_pos.y, dim, dim),
+            Color::from_rgb_u32(0xbfbfbf),
+        )?;
+    }
+
+    for (fruit, pos) in fruit {
+        let px_pos = match pos {
+            Some(pos) => pos.as_vec2() * dim + radius + top_left,
+            None => continue,
+        };
+        builder.circle(
+            fill,
+            px_pos,
+            radius,
+            CIRCLE_TOLERANCE,
+            fruit_color(fruit),
+        )?;
+        // Oranges are ringed like the shield they give
+        if fruit == Fruit::Orange {
+            builder.circle(
+                graphics::DrawMode::Stroke(graphics::StrokeOptions::default()
+                    .with_line_width(dim / 8.0)
+                ),
+                px_pos,
+                radius,
+                CIRCLE_TOLERANCE,
+                Color::from_rgb_u32(0xbfbfbf),
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// Builds one mesh holding the play area and everything on it.
+fn build_board(
+    ctx: &mut Context,
+    geo: &GeoConfig,
+    sim: &Simulation,
+) -> GameResult<Mesh> {
+    let config = &sim.config;
+    let mut builder = graphics::MeshBuilder::new();
+    build_play_area(&mut builder, geo, config.dimensions, config.wrap)?;
+    build_snake(
+        &mut builder,
+        geo,
+        &sim.snake,
+        sim.shielded,
+        config.wrap,
+        Color::GREEN,
+    )?;
+    build_items(&mut builder, geo, &sim.stone_walls, [
+        (Fruit::Apple, Some(sim.apple)),
+        (Fruit::Blueberry, sim.blueberry),
+        (Fruit::GoldenApple, sim.golden_apple),
+        (Fruit::Orange, sim.orange),
+    ])?;
+    builder.build(ctx)
+}
+
+/// Builds one mesh holding a versus board and everything on it.
+fn build_versus_board(
+    ctx: &mut Context,
+    geo: &GeoConfig,
+    versus: &Versus,
+) -> GameResult<Mesh> {
+    let config = &versus.config;
+    let mut builder = graphics::MeshBuilder::new();
+    build_play_area(&mut builder, geo, config.dimensions, config.wrap)?;
+    for (index, player) in versus.players.iter().enumerate() {
+        build_snake(
+            &mut builder,
+            geo,
+            &player.snake,
+            player.shielded,
+            config.wrap,
+            player_color(index),
+        )?;
+    }
+    build_items(&mut builder, geo, &versus.stone_walls, [
+        (Fruit::Apple, Some(versus.apple)),
+        (Fruit::Blueberry, versus.blueberry),
+        (Fruit::GoldenApple, versus.golden_apple),
+        (Fruit::Orange, versus.orange),
+    ])?;
+    builder.build(ctx)
+}
+
+/// Text size for menu screens, scaled to the window height.
+pub fn menu_text_size(ctx: &Context) -> f32 {
+    graphics::drawable_size(ctx).1 / 24.0
+}
+
+pub fn screen_center(ctx: &Context) -> Vec2 {
+    let (width, height) = graphics::drawable_size(ctx);
+    Vec2::new(width, height) / 2.0
+}
@@ -0,0 +1,273 @@
+use crate::config::{Config, ConfigError};
+use crate::simulation::{Direction, Simulation, Turn};
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+const MAGIC: &str = "snake-replay";
+const VERSION: u32 = 3;
+const CONFIG_HEADER: &str = "[config]";
+const REPLAY_DIR: &str = "replays";
+
+/// Everything needed to reproduce a run: the config, the seed and every
+/// committed turn. The final tick and score are stored so playback can
+/// confirm it matched.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Replay {
+    pub config: Config,
+    pub seed: u64,
+    pub ticks: u64,
+    pub score: u32,
+    pub turns: Vec<Turn>,
+}
+
+#[derive(Debug)]
+pub enum ReplayError {
+    NoDataDir,
+    Io(io::Error),
+    UnsupportedVersion(u32),
+    Config(ConfigError),
+    Parse { line: usize, message: String },
+}
+
+/// Feeds a recorded run back into a `Simulation`, one frame at a time.
+pub struct Playback {
+    pub replay: Replay,
+    next: usize,
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReplayError::NoDataDir => write!(f, "no user data directory"),
+            ReplayError::Io(err) => write!(f, "{}", err),
+            ReplayError::UnsupportedVersion(version) => write!(
+                f,
+                "unsupported replay version {} (expected {})",
+                version, VERSION,
+            ),
+            ReplayError::Config(err) => write!(f, "config: {}", err),
+            ReplayError::Parse { line, message } => {
+                write!(f, "line {}: {}", line, message)
+            }
+        }
+    }
+}
+
+impl From<io::Error> for ReplayError {
+    fn from(err: io::Error) -> Self {
+        ReplayError::Io(err)
+    }
+}
+
+impl Replay {
+    pub fn from_simulation(sim: &Simulation) -> Self {
+        Self {
+            config: sim.config.clone(),
+            seed: sim.seed,
+            ticks: sim.tick,
+            score: sim.score,
+            turns: sim.turns.clone(),
+        }
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut text = format!("{} {}\n", MAGIC, VERSION);
+        text += &format!("seed {}\n", self.seed);
+        text += &format!("ticks {}\n", self.ticks);
+        text += &format!("score {}\n", self.score);
+        for turn in &self.turns {
+            text += &format!(
+                "turn {} {}\n",
+                turn.tick,
+                turn.direction.name(),
+            );
+        }
+        text += CONFIG_HEADER;
+        text += "\n";
+        text += &self.config.to_toml();
+        text
+    }
+
+    /// Parses a replay. Older versions are rejected because the game placed
+    /// things differently, so they would not play back as recorded.
+    pub fn from_text(text: &str) -> Result<Self, ReplayError> {
+        let (text, config_text) = match text
+            .split_once(&format!("\n{}\n", CONFIG_HEADER))
+        {
+            Some((text, config_text)) => (text, Some(config_text)),
+            None => (text, None),
+        };
+        let mut lines = text.lines().enumerate()
+            .map(|(i, line)| (i + 1, line.trim()))
+            .filter(|(_, line)| !line.is_empty());
+        let parse_err = |line: usize, message: &str| ReplayError::Parse {
+            line,
+            message: message.to_string(),
+        };
+
+        let (line, header) = lines.next()
+            .ok_or_else(|| parse_err(1, "empty replay file"))?;
+        let version = match header.split_once(' ') {
+            Some((MAGIC, version)) => version.parse::<u32>()
+                .map_err(|_| parse_err(line, "invalid version"))?,
+            _ => return Err(parse_err(line, "not a replay file")),
+        };
+        let config = match (version, config_text) {
+            (VERSION, Some(config_text)) => Config::from_toml(config_text, None)
+                .map_err(ReplayError::Config)?,
+            (VERSION, None) => {
+                return Err(parse_err(line, "missing config section"));
+            }
+            _ => return Err(ReplayError::UnsupportedVersion(version)),
+        };
+
+        let mut seed = None;
+        let mut ticks = None;
+        let mut score = None;
+        let mut turns: Vec<Turn> = Vec::new();
+        for (line, content) in lines {
+            let fields: Vec<&str> = content.split_whitespace().collect();
+            match fields.as_slice() {
+                ["seed", value] => seed = Some(value.parse()
+                    .map_err(|_| parse_err(line, "invalid seed"))?),
+                ["ticks", value] => ticks = Some(value.parse()
+                    .map_err(|_| parse_err(line, "invalid tick count"))?),
+                ["score", value] => score = Some(value.parse()
+                    .map_err(|_| parse_err(line, "invalid score"))?),
+                ["turn", tick, direction] => {
+                    let tick = tick.parse()
+                        .map_err(|_| parse_err(line, "invalid turn tick"))?;
+                    if turns.last().is_some_and(|last| last.tick >= tick) {
+                        return Err(parse_err(line, "turns out of order"));
+                    }
+                    let direction = Direction::from_name(direction)
+                        .ok_or_else(|| parse_err(line, "invalid direction"))?;
+                    turns.push(Turn { tick, direction });
+                }
+                _ => return Err(parse_err(line, "unrecognized entry")),
+            }
+        }
+
+        Ok(Self {
+            config,
+            seed: seed.ok_or_else(|| parse_err(line, "missing seed"))?,
+            ticks: ticks.ok_or_else(|| parse_err(line, "missing tick count"))?,
+            score: score.ok_or_else(|| parse_err(line, "missing score"))?,
+            turns,
+        })
+    }
+
+    pub fn load(path: &Path) -> Result<Self, ReplayError> {
+        Self::from_text(&fs::read_to_string(path)?)
+    }
+
+    /// Where replays are saved, in the data directory beside the score
+    /// table.
+    pub fn dir() -> Result<PathBuf, ReplayError> {
+        let dir = dirs::data_dir().ok_or(ReplayError::NoDataDir)?;
+        Ok(dir.join("snake-game").join(REPLAY_DIR))
+    }
+
+    /// The most recently saved replay, if any.
+    pub fn latest() -> Option<PathBuf> {
+        fs::read_dir(Self::dir().ok()?).ok()?
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| {
+                entry.path().extension().is_some_and(|ext| ext == "replay")
+            })
+            .max_by_key(|entry| entry.metadata().and_then(|meta| meta.modified()).ok())
+            .map(|entry| entry.path())
+    }
+
+    /// Writes the replay to a new file in the replay directory and returns
+    /// its path.
+    pub fn save(&self) -> Result<PathBuf, ReplayError> {
+        let dir = Self::dir()?;
+        fs::create_dir_all(&dir)?;
+        let timestamp = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_secs());
+        let path = dir.join(format!("{}-{}.replay", timestamp, self.seed));
+        fs::write(&path, self.to_text())?;
+        Ok(path)
+    }
+}
+
+impl Playback {
+    pub fn new(replay: Replay) -> Self {
+        Self {
+            replay,
+            next: 0,
+        }
+    }
+
+    /// The recorded turn to apply before the simulation's next frame, if
+    /// any. The first turn is released straight away since the simulation
+    /// does not count frames until it has a direction.
+    pub fn next_input(&mut self, sim: &Simulation) -> Option<Direction> {
+        let turn = self.replay.turns.get(self.next)?;
+        if !sim.started() || turn.tick <= sim.tick {
+            self.next += 1;
+            Some(turn.direction)
+        } else {
+            None
+        }
+    }
+
+    /// Whether the finished simulation ended exactly as recorded.
+    pub fn matches(&self, sim: &Simulation) -> bool {
+        sim.tick == self.replay.ticks && sim.score == self.replay.score
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::autopilot::Autopilot;
+    use crate::simulation::StepOutcome;
+
+    use glam::IVec2;
+
+    fn config() -> Config {
+        Config {
+            dimensions: IVec2::new(20, 12),
+            ..Config::default()
+        }
+    }
+
+    /// Plays a run to the end with the autopilot and records it.
+    fn record(seed: u64) -> Replay {
+        let mut sim = Simulation::new(seed, config());
+        let mut autopilot = Autopilot::new();
+        while sim.live {
+            let input = autopilot.next_input(&sim);
+            sim.step(input);
+        }
+        Replay::from_simulation(&sim)
+    }
+
+    #[test]
+    fn text_round_trips() {
+        let replay = record(3);
+        assert!(!replay.turns.is_empty());
+        let parsed = Replay::from_text(&replay.to_text()).unwrap();
+        assert_eq!(parsed, replay);
+    }
+
+    #[test]
+    fn playback_reproduces_the_run() {
+        for seed in 0..5 {
+            let mut playback = Playback::new(record(seed));
+            let replay = &playback.replay;
+            let mut sim = Simulation::new(replay.seed, replay.config.clone());
+            while sim.live {
+                let input = playback.next_input(&sim);
+                assert_ne!(sim.step(input), StepOutcome::Idle);
+            }
+            assert!(playback.matches(&sim));
+        }
+    }
+}
@@ -0,0 +1,490 @@
+//! Versus over the network with rollback.
+//!
+//! Lockstep waits each tick for the other side's input, so every bit of
+//! latency shows. Here a tick runs at once on a prediction of the other
+//! side's input instead: that it pressed nothing, so its snake carries on
+//! the way it was going. Turns are rare next to ticks, so the prediction
+//! is usually right. When an input arrives that differs from what was
+//! predicted, the round is put back to the last tick both sides' inputs
+//! were known for and run forward again with the real input.
+//!
+//! Packets may be lost, delayed or reordered. Each packet carries every
+//! input the other side has not acknowledged yet, so a lost packet is made
+//! up for by the next one, and its sender's checksum of the last tick it
+//! has both inputs for, which catches a desync as in lockstep.
+
+use crate::netplay::{
+    is_hang_up, read_in_background, send_message, Message, NetError,
+};
+use crate::simulation::{Direction, StepOutcome};
+use crate::versus::{Versus, VersusResult, PLAYERS};
+
+use serde::{Deserialize, Serialize};
+
+use std::collections::BTreeMap;
+use std::io::BufReader;
+use std::net::TcpStream;
+use std::sync::mpsc::{Receiver, TryRecvError};
+
+/// How many ticks may run on predictions before waiting for the other
+/// side to catch up.
+pub const MAX_ROLLBACK: u64 = 12;
+
+/// What the other side is taken to have pressed on a tick its input has
+/// not arrived for.
+const PREDICTION: Option<Direction> = None;
+
+/// What each side sends the other once per tick.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Packet {
+    /// The tick of the first input in `inputs`.
+    pub start: u64,
+    /// The sender's inputs from `start` up to its latest tick.
+    pub inputs: Vec<Option<Direction>>,
+    /// How many of the receiver's inputs the sender has, from tick 0.
+    pub ack: u64,
+    /// The last tick the sender has both inputs for.
+    pub confirmed: u64,
+    /// The sender's checksum at `confirmed`.
+    pub checksum: u64,
+}
+
+/// Carries packets between the two sides. Packets need not all arrive,
+/// nor arrive in order.
+pub trait Transport {
+    fn send(&mut self, packet: &Packet) -> Result<(), NetError>;
+
+    /// The next packet to have arrived, if there is one. Never blocks.
+    fn receive(&mut self) -> Result<Option<Packet>, NetError>;
+}
+
+/// One side of a versus round played with rollback.
+pub struct Rollback<T> {
+    /// The round up to the latest tick, partly predicted.
+    pub versus: Versus,
+    /// The player this side steers. The host is the first player.
+    pub local: usize,
+    /// How many times a wrong prediction has been rolled back.
+    pub rollbacks: u64,
+    /// The round up to `confirmed_tick`, run on real inputs only.
+    confirmed: Versus,
+    confirmed_tick: u64,
+    transport: T,
+    /// How many ticks have run.
+    tick: u64,
+    /// Every local input, by tick.
+    local_inputs: Vec<Option<Direction>>,
+    /// The other side's inputs by tick, as far as they have arrived
+    /// without a gap.
+    remote_inputs: Vec<Option<Direction>>,
+    /// How many local inputs the other side has.
+    acked: u64,
+    /// Checksums of confirmed ticks the other side may yet report on.
+    checksums: BTreeMap<u64, u64>,
+    /// Checksums the other side sent for ticks not confirmed here yet.
+    remote_checksums: BTreeMap<u64, u64>,
+    /// Whether the other side has hung up.
+    hung_up: bool,
+}
+
+impl<T: Transport> Rollback<T> {
+    pub fn new(versus: Versus, local: usize, transport: T) -> Self {
+        let checksums = BTreeMap::from([(0, versus.checksum())]);
+        Self {
+            confirmed: versus.clone(),
+            versus,
+            local,
+            rollbacks: 0,
+            confirmed_tick: 0,
+            transport,
+            tick: 0,
+            local_inputs: Vec::new(),
+            remote_inputs: Vec::new(),
+            acked: 0,
+            checksums,
+            remote_checksums: BTreeMap::new(),
+            hung_up: false,
+        }
+    }
+
+    /// How the round ended, once both sides' inputs up to the end are in.
+    pub fn result(&self) -> Option<VersusResult> {
+        self.confirmed.result
+    }
+
+    /// Takes in whatever the other side has sent, then runs the next tick
+    /// with `input`. Never blocks. Returns `None` if the tick could not
+    /// run, because the round is over or the other side is `MAX_ROLLBACK`
+    /// ticks behind.
+    pub fn advance(
+        &mut self,
+        input: Option<Direction>,
+    ) -> Result<Option<StepOutcome>, NetError> {
+        self.receive()?;
+        // The other side hangs up once it has seen the round end, so its
+        // inputs only run out early if it left partway.
+        let behind = (self.remote_inputs.len() as u64) > self.tick;
+        if self.hung_up && !behind && self.result().is_none() {
+            return Err(NetError::Disconnected);
+        }
+        let outcome = if self.result().is_some() ||
+            self.tick - self.confirmed_tick >= MAX_ROLLBACK
+        {
+            None
+        } else {
+            self.local_inputs.push(input);
+            let outcome = self.versus.step(self.inputs_at(self.tick));
+            self.tick += 1;
+            self.confirm()?;
+            Some(outcome)
+        };
+        // Sent even when no tick ran, in case the last packet was lost.
+        match self.send() {
+            Err(err) if is_hang_up(&err) => (),
+            sent => sent?,
+        }
+        Ok(outcome)
+    }
+
+    /// Both inputs for `tick`, with the other side's predicted if it has
+    /// not arrived.
+    fn inputs_at(&self, tick: u64) -> [Option<Direction>; PLAYERS] {
+        let mut inputs = [None; PLAYERS];
+        inputs[self.local] = self.local_inputs[tick as usize];
+        inputs[1 - self.local] = self.remote_inputs.get(tick as usize)
+            .copied()
+            .unwrap_or(PREDICTION);
+        inputs
+    }
+
+    /// Reads every packet that has arrived, and rolls back if any of them
+    /// shows a prediction was wrong.
+    fn receive(&mut self) -> Result<(), NetError> {
+        let mut mispredicted = false;
+        loop {
+            let packet = match self.transport.receive() {
+                Ok(Some(packet)) => packet,
+                Ok(None) => break,
+                Err(NetError::Disconnected) => {
+                    self.hung_up = true;
+                    break;
+                }
+                Err(err) => return Err(err),
+            };
+            let sent = self.local_inputs.len() as u64;
+            self.acked = self.acked.max(packet.ack.min(sent));
+            for (tick, &input) in (packet.start..).zip(&packet.inputs) {
+                // Inputs already in are skipped, and a gap before an input
+                // leaves it for a later packet.
+                if tick != self.remote_inputs.len() as u64 {
+                    continue;
+                }
+                if tick < self.tick && input != PREDICTION {
+                    mispredicted = true;
+                }
+                self.remote_inputs.push(input);
+            }
+            self.check(packet.confirmed, packet.checksum)?;
+        }
+        self.confirm()?;
+        if mispredicted {
+            self.versus = self.confirmed.clone();
+            for tick in self.confirmed_tick..self.tick {
+                self.versus.step(self.inputs_at(tick));
+            }
+            self.rollbacks += 1;
+        }
+        Ok(())
+    }
+
+    /// Runs the confirmed round on through every tick both inputs are now
+    /// in for.
+    fn confirm(&mut self) -> Result<(), NetError> {
+        let known = self.tick.min(self.remote_inputs.len() as u64);
+        while self.confirmed_tick < known {
+            self.confirmed.step(self.inputs_at(self.confirmed_tick));
+            self.confirmed_tick += 1;
+            let checksum = self.confirmed.checksum();
+            self.checksums.insert(self.confirmed_tick, checksum);
+            if let Some(theirs) =
+                self.remote_checksums.remove(&self.confirmed_tick)
+            {
+                self.check(self.confirmed_tick, theirs)?;
+            }
+        }
+        if self.confirmed.result.is_some() {
+            // From here on the round shown is the confirmed one.
+            self.versus = self.confirmed.clone();
+        }
+        Ok(())
+    }
+
+    /// Compares the other side's checksum at `tick` with this side's, or
+    /// keeps it until this side has confirmed `tick`.
+    fn check(&mut self, tick: u64, theirs: u64) -> Result<(), NetError> {
+        if tick > self.confirmed_tick {
+            self.remote_checksums.insert(tick, theirs);
+            return Ok(());
+        }
+        if let Some(&ours) = self.checksums.get(&tick) {
+            if ours != theirs {
+                return Err(NetError::Desync { tick });
+            }
+        }
+        // The other side has confirmed `tick`, so it will not report on
+        // anything before it again.
+        self.checksums = self.checksums.split_off(&tick);
+        Ok(())
+    }
+
+    fn send(&mut self) -> Result<(), NetError> {
+        let packet = Packet {
+            start: self.acked,
+            inputs: self.local_inputs[self.acked as usize..].to_vec(),
+            ack: self.remote_inputs.len() as u64,
+            confirmed: self.confirmed_tick,
+            checksum: self.checksums[&self.confirmed_tick],
+        };
+        self.transport.send(&packet)
+    }
+}
+
+/// Packets as lines on a TCP connection. TCP loses nothing, but it can
+/// hold packets up, which rollback hides.
+pub struct TcpTransport {
+    writer: TcpStream,
+    /// Packets read by a separate thread, so receiving never blocks.
+    packets: Receiver<Result<Packet, NetError>>,
+}
+
+impl TcpTransport {
+    /// Takes over a connection. `reader` reads from `stream`, and may
+    /// already hold what was sent after the hello.
+    pub(crate) fn new(
+        reader: BufReader<TcpStream>,
+        stream: TcpStream,
+    ) -> Self {
+        let packets = read_in_background(reader, |message| match message {
+            Message::Packet(packet) => Ok(packet),
+            message => Err(NetError::Protocol(format!(
+                "expected a packet, got {:?}",
+                message,
+            ))),
+        });
+        Self {
+            writer: stream,
+            packets,
+        }
+    }
+}
+
+impl Transport for TcpTransport {
+    fn send(&mut self, packet: &Packet) -> Result<(), NetError> {
+        send_message(&mut self.writer, &Message::Packet(packet.clone()))
+    }
+
+    fn receive(&mut self) -> Result<Option<Packet>, NetError> {
+        match self.packets.try_recv() {
+            Ok(packet) => packet.map(Some),
+            Err(TryRecvError::Empty) => Ok(None),
+            Err(TryRecvError::Disconnected) => Err(NetError::Disconnected),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    use glam::IVec2;
+
+    use rand::{Rng, SeedableRng};
+    use rand_chacha::ChaCha8Rng;
+
+    use std::cell::{Cell, RefCell};
+    use std::ops::RangeInclusive;
+    use std::rc::Rc;
+
+    /// Gives up on a round that has not ended by this many frames.
+    const MAX_FRAMES: u64 = 100_000;
+
+    /// Packets on their way to one side, each with the frame it arrives
+    /// on.
+    type Queue = Rc<RefCell<Vec<(u64, Packet)>>>;
+
+    /// How the in-process link treats packets.
+    #[derive(Clone)]
+    struct LinkConditions {
+        /// How many frames a packet takes to arrive.
+        delay: RangeInclusive<u64>,
+        /// The chance of a packet never arriving.
+        loss: f64,
+    }
+
+    const PERFECT: LinkConditions = LinkConditions { delay: 0..=0, loss: 0.0 };
+
+    /// One end of a link within the test, on a shared frame clock.
+    struct LocalTransport {
+        outbox: Queue,
+        inbox: Queue,
+        clock: Rc<Cell<u64>>,
+        conditions: LinkConditions,
+        rng: ChaCha8Rng,
+    }
+
+    impl Transport for LocalTransport {
+        fn send(&mut self, packet: &Packet) -> Result<(), NetError> {
+            if !self.rng.gen_bool(self.conditions.loss) {
+                let delay = self.rng.gen_range(self.conditions.delay.clone());
+                let arrival = self.clock.get() + delay;
+                self.outbox.borrow_mut().push((arrival, packet.clone()));
+            }
+            Ok(())
+        }
+
+        fn receive(&mut self) -> Result<Option<Packet>, NetError> {
+            let mut inbox = self.inbox.borrow_mut();
+            let now = self.clock.get();
+            Ok(inbox.iter()
+                .position(|&(arrival, _)| arrival <= now)
+                .map(|index| inbox.remove(index).1))
+        }
+    }
+
+    /// Two sides of a round linked in-process, with the first as host.
+    struct Match {
+        sides: [Rollback<LocalTransport>; PLAYERS],
+        clock: Rc<Cell<u64>>,
+    }
+
+    fn config() -> Config {
+        Config {
+            dimensions: IVec2::new(20, 12),
+            ..Config::default()
+        }
+    }
+
+    impl Match {
+        fn new(seed: u64, conditions: LinkConditions) -> Self {
+            let clock = Rc::new(Cell::new(0));
+            let queues: [Queue; PLAYERS] = Default::default();
+            let sides = [0, 1].map(|local| {
+                let transport = LocalTransport {
+                    outbox: queues[1 - local].clone(),
+                    inbox: queues[local].clone(),
+                    clock: clock.clone(),
+                    conditions: conditions.clone(),
+                    rng: ChaCha8Rng::seed_from_u64(seed * 2 + local as u64),
+                };
+                let versus = Versus::new(seed, config());
+                Rollback::new(versus, local, transport)
+            });
+            Self { sides, clock }
+        }
+
+        /// Runs both sides a frame at a time until each has seen the round
+        /// end, each steering with the built-in player on its own view.
+        fn play(&mut self) -> Result<(), NetError> {
+            while self.sides.iter().any(|side| side.result().is_none()) {
+                assert!(self.clock.get() < MAX_FRAMES, "round never ended");
+                for side in &mut self.sides {
+                    let input = side.versus.greedy_direction(side.local);
+                    side.advance(input)?;
+                }
+                self.clock.set(self.clock.get() + 1);
+            }
+            Ok(())
+        }
+
+        /// Checks both sides ended the round exactly as a plain `Versus`
+        /// run on the inputs each side really sent.
+        fn assert_agrees_with_reference(&self, seed: u64) {
+            let [host, guest] = &self.sides;
+            let end = host.confirmed_tick.min(guest.confirmed_tick);
+            let mut reference = Versus::new(seed, config());
+            for tick in 0..end as usize {
+                reference.step([
+                    host.local_inputs[tick],
+                    guest.local_inputs[tick],
+                ]);
+            }
+            assert!(reference.result.is_some());
+            for side in &self.sides {
+                assert_eq!(side.result(), reference.result);
+                assert_eq!(side.versus.checksum(), reference.checksum());
+                assert_eq!(side.confirmed.checksum(), reference.checksum());
+            }
+        }
+    }
+
+    #[test]
+    fn snapshot_round_trips_through_json() {
+        let mut versus = Versus::new(7, config());
+        let mut copy = None;
+        for tick in 0..2_000 {
+            if tick == 500 {
+                let json = serde_json::to_string(&versus).unwrap();
+                copy = Some(serde_json::from_str::<Versus>(&json).unwrap());
+            }
+            let inputs = [0, 1].map(|index| versus.greedy_direction(index));
+            versus.step(inputs);
+            if let Some(copy) = &mut copy {
+                copy.step(inputs);
+                assert_eq!(copy.checksum(), versus.checksum());
+            }
+        }
+    }
+
+    #[test]
+    fn perfect_link_agrees_with_reference() {
+        for seed in 0..5 {
+            let mut game = Match::new(seed, PERFECT);
+            game.play().unwrap();
+            game.assert_agrees_with_reference(seed);
+        }
+    }
+
+    #[test]
+    fn jitter_agrees_with_reference() {
+        let jitter = LinkConditions { delay: 0..=8, loss: 0.0 };
+        for seed in 0..5 {
+            let mut game = Match::new(seed, jitter.clone());
+            game.play().unwrap();
+            game.assert_agrees_with_reference(seed);
+            assert!(game.sides.iter().all(|side| side.rollbacks > 0));
+        }
+    }
+
+    #[test]
+    fn packet_loss_agrees_with_reference() {
+        let lossy = LinkConditions { delay: 1..=5, loss: 0.3 };
+        for seed in 0..5 {
+            let mut game = Match::new(seed, lossy.clone());
+            game.play().unwrap();
+            game.assert_agrees_with_reference(seed);
+        }
+    }
+
+    #[test]
+    fn waits_when_too_far_ahead() {
+        let dead = LinkConditions { delay: 0..=0, loss: 1.0 };
+        let mut game = Match::new(1, dead);
+        let host = &mut game.sides[0];
+        for _ in 0..MAX_ROLLBACK {
+            assert!(host.advance(Some(Direction::UP)).unwrap().is_some());
+        }
+        assert!(host.advance(Some(Direction::UP)).unwrap().is_none());
+    }
+
+    #[test]
+    fn desync_is_caught() {
+        let lossy = LinkConditions { delay: 0..=3, loss: 0.1 };
+        let mut game = Match::new(3, lossy);
+        game.sides[1].confirmed.players[0].score += 1;
+        match game.play() {
+            Err(NetError::Desync { .. }) => (),
+            other => panic!("expected a desync, got {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,162 @@
+use crate::simulation::Simulation;
+
+use serde::{Deserialize, Serialize};
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+const MAX_ENTRIES: usize = 10;
+const SCORES_FILE: &str = "scores.toml";
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ScoreEntry {
+    pub score: u32,
+    pub date: String,
+    pub seed: u64,
+    pub length: usize,
+    pub duration_secs: f32,
+}
+
+/// The best scores on this machine, highest first, and every run that
+/// filled the board, oldest first.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct HighScores {
+    #[serde(default)]
+    pub entries: Vec<ScoreEntry>,
+    #[serde(default)]
+    pub wins: Vec<ScoreEntry>,
+}
+
+#[derive(Debug)]
+pub enum ScoresError {
+    NoDataDir,
+    Io(io::Error),
+    Parse(toml::de::Error),
+}
+
+impl fmt::Display for ScoresError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScoresError::NoDataDir => write!(f, "no user data directory"),
+            ScoresError::Io(err) => write!(f, "{}", err),
+            ScoresError::Parse(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl From<io::Error> for ScoresError {
+    fn from(err: io::Error) -> Self {
+        ScoresError::Io(err)
+    }
+}
+
+impl ScoreEntry {
+    pub fn from_simulation(sim: &Simulation) -> Self {
+        Self {
+            score: sim.score,
+            date: chrono::Local::now().format("%Y-%m-%d").to_string(),
+            seed: sim.seed,
+            length: sim.snake.len(),
+            duration_secs: sim.tick as f32 / sim.config.target_fps as f32,
+        }
+    }
+}
+
+/// A run's time as minutes and whole seconds, such as "3:07".
+pub fn format_duration(duration_secs: f32) -> String {
+    let secs = duration_secs as u32;
+    format!("{}:{:02}", secs / 60, secs % 60)
+}
+
+impl HighScores {
+    pub fn path() -> Result<PathBuf, ScoresError> {
+        let dir = dirs::data_dir().ok_or(ScoresError::NoDataDir)?;
+        Ok(dir.join("snake-game").join(SCORES_FILE))
+    }
+
+    /// Reads the table from the data directory. A missing file is an empty
+    /// table.
+    pub fn load() -> Result<Self, ScoresError> {
+        let text = match fs::read_to_string(Self::path()?) {
+            Ok(text) => text,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                return Ok(Self::default());
+            }
+            Err(err) => return Err(err.into()),
+        };
+        let mut scores: Self = toml::from_str(&text)
+            .map_err(ScoresError::Parse)?;
+        scores.entries.truncate(MAX_ENTRIES);
+        Ok(scores)
+    }
+
+    pub fn save(&self) -> Result<(), ScoresError> {
+        let path = Self::path()?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let text = toml::to_string(self).expect("scores are always serializable");
+        fs::write(path, text)?;
+        Ok(())
+    }
+
+    pub fn best(&self) -> Option<u32> {
+        self.entries.first().map(|entry| entry.score)
+    }
+
+    /// Records a run that filled the board. Wins are kept apart from the
+    /// score table, since a full board says more than the score.
+    pub fn record_win(&mut self, entry: ScoreEntry) {
+        self.wins.push(entry);
+    }
+
+    /// Adds `entry` if it makes the table and returns its rank, counting
+    /// from zero. Ties rank below existing entries.
+    pub fn insert(&mut self, entry: ScoreEntry) -> Option<usize> {
+        let rank = self.entries.iter()
+            .position(|existing| existing.score < entry.score)
+            .unwrap_or(self.entries.len());
+        if rank >= MAX_ENTRIES {
+            return None;
+        }
+        self.entries.insert(rank, entry);
+        self.entries.truncate(MAX_ENTRIES);
+        Some(rank)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(score: u32) -> ScoreEntry {
+        ScoreEntry {
+            score,
+            date: "2024-01-01".to_string(),
+            seed: 1,
+            length: 1,
+            duration_secs: 187.9,
+        }
+    }
+
+    #[test]
+    fn wins_stay_out_of_the_table() {
+        let mut scores = HighScores::default();
+        for score in [5, 3] {
+            scores.insert(entry(score));
+        }
+        scores.record_win(entry(900));
+        assert_eq!(scores.wins, [entry(900)]);
+        assert_eq!(scores.entries, [entry(5), entry(3)]);
+        assert_eq!(scores.best(), Some(5));
+    }
+
+    #[test]
+    fn durations_round_down_to_whole_seconds() {
+        assert_eq!(format_duration(187.9), "3:07");
+        assert_eq!(format_duration(59.99), "0:59");
+        assert_eq!(format_duration(600.0), "10:00");
+    }
+}
@@ -0,0 +1,122 @@
+use super::menu::Menu;
+use super::{Playing, Screen, Shared, Transition};
+use crate::campaign::Campaign;
+use crate::render;
+
+use ggez::{Context, GameResult};
+use ggez::event::KeyCode;
+use ggez::graphics::Color;
+
+use glam::Vec2;
+
+/// The campaign levels in order, with their goals. A level can be played
+/// once the one before it is cleared.
+pub struct CampaignSelect {
+    menu: Menu,
+    message: Option<String>,
+}
+
+impl CampaignSelect {
+    /// Opens on the first level not yet cleared.
+    pub fn new(campaign: &Campaign, shared: &Shared) -> Self {
+        let mut items: Vec<String> = campaign.levels.iter()
+            .enumerate()
+            .map(|(index, level)| {
+                if shared.progress.is_cleared(&level.name) {
+                    format!("{} (cleared)", level.name)
+                } else if campaign.is_unlocked(&shared.progress, index) {
+                    level.name.clone()
+                } else {
+                    format!("{} (locked)", level.name)
+                }
+            })
+            .collect();
+        items.push("Back".to_string());
+        let selected = campaign.levels.iter()
+            .position(|level| !shared.progress.is_cleared(&level.name))
+            .unwrap_or(0);
+        Self {
+            menu: Menu { items, selected },
+            message: None,
+        }
+    }
+}
+
+impl Screen for CampaignSelect {
+    fn update(&mut self, _ctx: &mut Context, _shared: &mut Shared) -> Transition {
+        Transition::None
+    }
+
+    fn draw(&mut self, ctx: &mut Context, shared: &Shared) -> GameResult<()> {
+        let center = render::screen_center(ctx);
+        let text_size = render::menu_text_size(ctx);
+        render::queue_centered(
+            ctx,
+            "CAMPAIGN",
+            center - Vec2::new(0.0, text_size * 4.0),
+            text_size * 2.0,
+            Color::WHITE,
+        );
+        self.menu.queue(ctx, center, text_size);
+        let below = center + Vec2::new(
+            0.0,
+            (self.menu.items.len() as f32 * 1.5 + 1.0) * text_size,
+        );
+        let goal = shared.campaign.as_ref()
+            .and_then(|campaign| campaign.levels.get(self.menu.selected))
+            .and_then(|level| level.config.goal);
+        if let Some(goal) = goal {
+            render::queue_centered(
+                ctx,
+                &format!("goal: {}", goal.describe()),
+                below,
+                text_size * 0.75,
+                Color::from_rgb_u32(0xbfbfbf),
+            );
+        }
+        if let Some(message) = &self.message {
+            render::queue_centered(
+                ctx,
+                message,
+                below + Vec2::new(0.0, text_size * 1.5),
+                text_size * 0.75,
+                Color::RED,
+            );
+        }
+        Ok(())
+    }
+
+    fn key_down(
+        &mut self,
+        ctx: &mut Context,
+        shared: &mut Shared,
+        keycode: KeyCode,
+    ) -> Transition {
+        if keycode == KeyCode::Escape {
+            return Transition::Pop;
+        }
+        let chosen = self.menu.key_down(keycode);
+        self.message = None;
+        let campaign = match &shared.campaign {
+            Some(campaign) => campaign,
+            None => return Transition::Pop,
+        };
+        match chosen {
+            Some(index) if index < campaign.levels.len() => {
+                if campaign.is_unlocked(&shared.progress, index) {
+                    Transition::Reset(Box::new(
+                        Playing::campaign(ctx, shared, index),
+                    ))
+                } else {
+                    self.message = Some(format!(
+                        "clear {} to unlock this level",
+                        campaign.levels[index - 1].name,
+                    ));
+                    Transition::None
+                }
+            }
+            Some(_) => Transition::Pop,
+            None => Transition::None,
+        }
+    }
+}
@@ -0,0 +1,435 @@
+use super::{Playing, Screen, Shared, Transition};
+use crate::config::Fruit;
+use crate::level::{Level, LevelError};
+use crate::render::{self, GeoConfig};
+use crate::simulation::Direction;
+
+use ggez::{Context, GameResult};
+use ggez::event::{KeyCode, MouseButton};
+use ggez::graphics::{self, Color, Mesh, Rect, Text};
+use ggez::input::mouse;
+
+use glam::{IVec2, Vec2};
+
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// The level the title screen's editor works on.
+pub const DEFAULT_LEVEL_PATH: &str = "levels/custom.toml";
+
+/// What the left mouse button does on the board.
+#[derive(Copy, Clone, PartialEq)]
+enum Tool {
+    Wall,
+    Erase,
+    Start,
+    Fruit(Fruit),
+}
+
+/// Something in the strip below the board that can be clicked.
+#[derive(Copy, Clone, PartialEq)]
+enum Button {
+    Tool(Tool),
+    Save,
+    TestPlay,
+}
+
+/// Paints a level with the mouse and saves it as a level file. Left click
+/// uses the selected tool and right click erases. Walls can be painted by
+/// dragging.
+pub struct Editor {
+    path: PathBuf,
+    level: Level,
+    geo_config: GeoConfig,
+    tool: Tool,
+    /// Where each button was last drawn, so clicks can find it.
+    buttons: Vec<(Button, Rect)>,
+    message: Option<(String, Color)>,
+    /// Set while a test play runs on top of the editor.
+    testing: bool,
+}
+
+impl Tool {
+    const ALL: [Tool; 7] = [
+        Tool::Wall,
+        Tool::Erase,
+        Tool::Start,
+        Tool::Fruit(Fruit::Apple),
+        Tool::Fruit(Fruit::Blueberry),
+        Tool::Fruit(Fruit::GoldenApple),
+        Tool::Fruit(Fruit::Orange),
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Tool::Wall => "wall",
+            Tool::Erase => "erase",
+            Tool::Start => "start",
+            Tool::Fruit(fruit) => fruit.name(),
+        }
+    }
+
+    /// Whether holding the button down and moving paints more squares.
+    fn drags(self) -> bool {
+        matches!(self, Tool::Wall | Tool::Erase)
+    }
+}
+
+/// Queues `text` with its top-left corner at `pos` and returns the area it
+/// covers.
+fn queue_text(
+    ctx: &mut Context,
+    text: &str,
+    pos: Vec2,
+    size: f32,
+    color: Color,
+) -> Rect {
+    let mut text = Text::new(text);
+    text.set_font(graphics::Font::default(), graphics::PxScale::from(size));
+    let bounds = text.dimensions(ctx);
+    graphics::queue_text(ctx, &text, pos, Some(color));
+    Rect::new(pos.x, pos.y, bounds.w, bounds.h)
+}
+
+impl Editor {
+    /// Opens the level at `path`, or a blank board the size of the shared
+    /// config if there is no level there yet.
+    pub fn open(ctx: &Context, shared: &Shared, path: PathBuf) -> Self {
+        let mut message = None;
+        let level = match Level::load_draft(&path) {
+            Ok(level) => level,
+            Err(err) => {
+                let missing = matches!(
+                    &err,
+                    LevelError::Io(err) if err.kind() == io::ErrorKind::NotFound
+                );
+                if !missing {
+                    message = Some((
+                        format!("{}: {}", path.display(), err),
+                        Color::RED,
+                    ));
+                }
+                blank_level(shared, &path)
+            }
+        };
+        Self {
+            geo_config: GeoConfig::new(ctx, level.config.dimensions),
+            path,
+            level,
+            tool: Tool::Wall,
+            buttons: Vec::new(),
+            message,
+            testing: false,
+        }
+    }
+
+    /// The board square under `pos`, in screen pixels.
+    fn square_at(&self, pos: Vec2) -> Option<IVec2> {
+        let geo = &self.geo_config;
+        let square = ((pos - geo.top_left) / geo.dim).floor().as_ivec2();
+        let dimensions = self.level.config.dimensions;
+        let on_board = square.cmpge(IVec2::ZERO).all() &&
+            square.cmplt(dimensions).all();
+        on_board.then_some(square)
+    }
+
+    fn paint(&mut self, square: IVec2, tool: Tool) {
+        let config = &mut self.level.config;
+        let is_wall = config.walls.contains(&square);
+        let is_start = config.start_pos() == square;
+        match tool {
+            Tool::Wall if !is_wall && !is_start => {
+                config.fruits.retain(|_, pos| *pos != square);
+                config.walls.push(square);
+            }
+            Tool::Erase => {
+                config.walls.retain(|pos| *pos != square);
+                config.fruits.retain(|_, pos| *pos != square);
+            }
+            Tool::Start if !is_wall => {
+                config.fruits.retain(|_, pos| *pos != square);
+                config.start = Some(square);
+            }
+            Tool::Fruit(fruit) if !is_wall && !is_start => {
+                config.fruits.retain(|_, pos| *pos != square);
+                config.fruits.insert(fruit, square);
+            }
+            _ => (),
+        }
+    }
+
+    /// Turns the start to set off in `direction`, or back to waiting for
+    /// the first turn if it already does.
+    fn set_start_direction(&mut self, direction: Direction) {
+        let config = &mut self.level.config;
+        config.start_direction = match config.start_direction {
+            Some(current) if current == direction => None,
+            _ => Some(direction),
+        };
+    }
+
+    fn save(&mut self) {
+        let result = self.level.check()
+            .and_then(|()| self.level.save(&self.path));
+        self.message = Some(match result {
+            Ok(()) => (
+                format!("saved to {}", self.path.display()),
+                Color::from_rgb_u32(0xbfbfbf),
+            ),
+            Err(err) => (err.to_string(), Color::RED),
+        });
+    }
+
+    fn test_play(&mut self, ctx: &mut Context, shared: &Shared) -> Transition {
+        if let Err(err) = self.level.check() {
+            self.message = Some((err.to_string(), Color::RED));
+            return Transition::None;
+        }
+        self.testing = true;
+        Transition::Push(Box::new(Playing::test_level(
+            ctx,
+            shared,
+            self.level.config.clone(),
+        )))
+    }
+
+    fn press(
+        &mut self,
+        ctx: &mut Context,
+        shared: &Shared,
+        button: Button,
+    ) -> Transition {
+        match button {
+            Button::Tool(tool) => self.tool = tool,
+            Button::Save => self.save(),
+            Button::TestPlay => return self.test_play(ctx, shared),
+        }
+        Transition::None
+    }
+
+    /// Builds the board with walls, fruit, the start and a grid to count
+    /// squares by.
+    fn build_board(&self, ctx: &mut Context) -> GameResult<Mesh> {
+        let dim = self.geo_config.dim;
+        let top_left = self.geo_config.top_left;
+        let radius = dim / 2.0;
+        let config = &self.level.config;
+        let size = config.dimensions.as_vec2() * dim;
+        let fill = graphics::DrawMode::Fill(graphics::FillOptions::default());
+        let mut builder = graphics::MeshBuilder::new();
+
+        builder.rectangle(
+            fill,
+            Rect::new(top_left.x, top_left.y, size.x, size.y),
+            Color::BLACK,
+        )?;
+        let grid_color = Color::from_rgb_u32(0x1c1c1c);
+        for x in 1..config.dimensions.x {
+            let top = top_left + Vec2::new(x as f32 * dim, 0.0);
+            builder.line(&[top, top + Vec2::new(0.0, size.y)], 1.0, grid_color)?;
+        }
+        for y in 1..config.dimensions.y {
+            let left = top_left + Vec2::new(0.0, y as f32 * dim);
+            builder.line(&[left, left + Vec2::new(size.x, 0.0)], 1.0, grid_color)?;
+        }
+
+        for pos in &config.walls {
+            let px_pos = pos.as_vec2() * dim + top_left;
+            builder.rectangle(
+                fill,
+                Rect::new(px_pos.x, px_pos.y, dim, dim),
+                Color::from_rgb_u32(0xbfbfbf),
+            )?;
+        }
+        for (fruit, pos) in &config.fruits {
+            builder.circle(
+                fill,
+                pos.as_vec2() * dim + radius + top_left,
+                radius,
+                render::CIRCLE_TOLERANCE,
+                render::fruit_color(*fruit),
+            )?;
+        }
+
+        // The start, with a line showing which way it sets off
+        let px_pos = config.start_pos().as_vec2() * dim + top_left;
+        builder.polygon(fill, &render::diamond(px_pos, dim), Color::GREEN)?;
+        if let Some(direction) = config.start_direction {
+            let center = px_pos + radius;
+            builder.line(
+                &[center, center + direction.delta().as_vec2() * radius],
+                dim / 6.0,
+                Color::BLACK,
+            )?;
+        }
+
+        let cursor = mouse::position(ctx);
+        if let Some(square) = self.square_at(Vec2::new(cursor.x, cursor.y)) {
+            let px_pos = square.as_vec2() * dim + top_left;
+            builder.rectangle(
+                graphics::DrawMode::Stroke(graphics::StrokeOptions::default()
+                    .with_line_width(2.0)
+                ),
+                Rect::new(px_pos.x, px_pos.y, dim, dim),
+                Color::WHITE,
+            )?;
+        }
+        builder.build(ctx)
+    }
+}
+
+/// An empty board with the shared config's size and rules, named after
+/// the file it will be saved to.
+fn blank_level(shared: &Shared, path: &Path) -> Level {
+    let mut config = shared.config.clone();
+    config.walls.clear();
+    config.fruits.clear();
+    config.start = None;
+    config.start_direction = None;
+    let name = path.file_stem()
+        .map_or("Untitled".to_string(), |stem| {
+            stem.to_string_lossy().into_owned()
+        });
+    Level { name, config }
+}
+
+impl Screen for Editor {
+    fn update(&mut self, _ctx: &mut Context, _shared: &mut Shared) -> Transition {
+        if self.testing {
+            self.testing = false;
+            self.message = Some((
+                "test play over".to_string(),
+                Color::from_rgb_u32(0xbfbfbf),
+            ));
+        }
+        Transition::None
+    }
+
+    fn draw(&mut self, ctx: &mut Context, _shared: &Shared) -> GameResult<()> {
+        let board = self.build_board(ctx)?;
+        graphics::draw(ctx, &board, graphics::DrawParam::default())?;
+
+        // Buttons and help in the strip below the board
+        let dim = self.geo_config.dim;
+        let text_size = dim.min(render::menu_text_size(ctx) * 0.75);
+        let left = self.geo_config.top_left.x + dim;
+        let mut pos = Vec2::new(
+            left,
+            self.geo_config.top_left.y +
+                dim * self.level.config.dimensions.y as f32 +
+                dim * 0.4,
+        );
+        let gap = text_size;
+        let grey = Color::from_rgb_u32(0xbfbfbf);
+        self.buttons.clear();
+        for (i, tool) in Tool::ALL.into_iter().enumerate() {
+            let color = if tool == self.tool { Color::WHITE } else { grey };
+            let label = format!("{} {}", i + 1, tool.name());
+            let area = queue_text(ctx, &label, pos, text_size, color);
+            self.buttons.push((Button::Tool(tool), area));
+            pos.x += area.w + gap;
+        }
+
+        pos = Vec2::new(left, pos.y + text_size * 1.3);
+        for (button, label) in [
+            (Button::Save, "save (S)"),
+            (Button::TestPlay, "test play (P)"),
+        ] {
+            let area = queue_text(ctx, label, pos, text_size, Color::WHITE);
+            self.buttons.push((button, area));
+            pos.x += area.w + gap;
+        }
+        let edges = if self.level.config.wrap { "wrap" } else { "solid" };
+        let help = format!(
+            "{}   edges {} (W)   arrows set off   right click erases",
+            self.level.name,
+            edges,
+        );
+        queue_text(ctx, &help, pos, text_size, grey);
+
+        if let Some((message, color)) = &self.message {
+            pos = Vec2::new(left, pos.y + text_size * 1.3);
+            queue_text(ctx, message, pos, text_size, *color);
+        }
+        Ok(())
+    }
+
+    fn key_down(
+        &mut self,
+        ctx: &mut Context,
+        shared: &mut Shared,
+        keycode: KeyCode,
+    ) -> Transition {
+        let tool = match keycode {
+            KeyCode::Key1 => Some(0),
+            KeyCode::Key2 => Some(1),
+            KeyCode::Key3 => Some(2),
+            KeyCode::Key4 => Some(3),
+            KeyCode::Key5 => Some(4),
+            KeyCode::Key6 => Some(5),
+            KeyCode::Key7 => Some(6),
+            _ => None,
+        };
+        if let Some(index) = tool {
+            self.tool = Tool::ALL[index];
+            return Transition::None;
+        }
+        match keycode {
+            KeyCode::Escape => return Transition::Pop,
+            KeyCode::S => self.save(),
+            KeyCode::P => return self.test_play(ctx, shared),
+            KeyCode::W => self.level.config.wrap = !self.level.config.wrap,
+            KeyCode::Up => self.set_start_direction(Direction::UP),
+            KeyCode::Down => self.set_start_direction(Direction::DOWN),
+            KeyCode::Left => self.set_start_direction(Direction::LEFT),
+            KeyCode::Right => self.set_start_direction(Direction::RIGHT),
+            _ => (),
+        }
+        Transition::None
+    }
+
+    fn mouse_down(
+        &mut self,
+        ctx: &mut Context,
+        shared: &mut Shared,
+        button: MouseButton,
+        pos: Vec2,
+    ) -> Transition {
+        if button == MouseButton::Left {
+            let clicked = self.buttons.iter()
+                .find(|(_, area)| area.contains(pos))
+                .map(|(clicked, _)| *clicked);
+            if let Some(clicked) = clicked {
+                return self.press(ctx, shared, clicked);
+            }
+        }
+        let tool = match button {
+            MouseButton::Left => self.tool,
+            MouseButton::Right => Tool::Erase,
+            _ => return Transition::None,
+        };
+        if let Some(square) = self.square_at(pos) {
+            self.paint(square, tool);
+        }
+        Transition::None
+    }
+
+    fn mouse_motion(&mut self, ctx: &mut Context, pos: Vec2) {
+        let tool = if mouse::button_pressed(ctx, MouseButton::Right) {
+            Tool::Erase
+        } else if mouse::button_pressed(ctx, MouseButton::Left) &&
+            self.tool.drags()
+        {
+            self.tool
+        } else {
+            return;
+        };
+        if let Some(square) = self.square_at(pos) {
+            self.paint(square, tool);
+        }
+    }
+
+    fn resize(&mut self, ctx: &mut Context) {
+        self.geo_config = GeoConfig::new(ctx, self.level.config.dimensions);
+    }
+}
@@ -0,0 +1,173 @@
+use super::playing::GameKind;
+use super::{Playing, Screen, Shared, Transition};
+use crate::render;
+use crate::scores;
+use crate::simulation::Simulation;
+
+use ggez::{Context, GameResult};
+use ggez::event::KeyCode;
+use ggez::graphics::{self, Color, Text};
+
+use glam::Vec2;
+
+/// Drawn over the board of a finished game.
+pub struct GameOver {
+    seed: u64,
+    /// How far the run got towards its goal, if it had one.
+    goal: Option<String>,
+    kind: GameKind,
+    new_rank: Option<usize>,
+    new_best: bool,
+}
+
+impl GameOver {
+    pub fn new(
+        sim: &Simulation,
+        kind: GameKind,
+        new_rank: Option<usize>,
+        new_best: bool,
+    ) -> Self {
+        let goal = match (sim.config.goal, sim.goal_progress()) {
+            (Some(goal), Some(progress)) => Some(format!(
+                "{}: {} of {}",
+                goal.describe(),
+                progress,
+                goal.target,
+            )),
+            _ => None,
+        };
+        Self {
+            seed: sim.seed,
+            goal,
+            kind,
+            new_rank,
+            new_best,
+        }
+    }
+
+    /// Queues the high-score table, starting at `pos`, highlighting the run
+    /// that just ended.
+    fn queue_high_scores(
+        &self,
+        ctx: &mut Context,
+        shared: &Shared,
+        pos: Vec2,
+        text_size: f32,
+    ) {
+        let mut pos = pos;
+        if self.new_best {
+            render::queue_centered(
+                ctx,
+                "NEW PERSONAL BEST",
+                pos,
+                text_size,
+                Color::YELLOW,
+            );
+            pos.y += text_size * 1.5;
+        }
+        let rows: Vec<Text> = shared.high_scores.entries.iter()
+            .enumerate()
+            .map(|(rank, entry)| {
+                let mut row = Text::new(format!(
+                    "{:>2}. {:>6}   length {:<5} {:>6}   {}   seed {}",
+                    rank + 1,
+                    entry.score,
+                    entry.length,
+                    scores::format_duration(entry.duration_secs),
+                    entry.date,
+                    entry.seed,
+                ));
+                row.set_font(
+                    graphics::Font::default(),
+                    graphics::PxScale::from(text_size),
+                );
+                row
+            })
+            .collect();
+        let width = rows.iter()
+            .map(|row| row.dimensions(ctx).w)
+            .fold(0.0, f32::max);
+        for (rank, row) in rows.iter().enumerate() {
+            let color = if self.new_rank == Some(rank) {
+                Color::YELLOW
+            } else {
+                Color::from_rgb_u32(0xbfbfbf)
+            };
+            graphics::queue_text(
+                ctx,
+                row,
+                Vec2::new(pos.x - width / 2.0, pos.y),
+                Some(color),
+            );
+            pos.y += text_size * 1.2;
+        }
+    }
+}
+
+impl Screen for GameOver {
+    fn update(&mut self, _ctx: &mut Context, _shared: &mut Shared) -> Transition {
+        Transition::None
+    }
+
+    fn draw(&mut self, ctx: &mut Context, shared: &Shared) -> GameResult<()> {
+        let center = render::screen_center(ctx);
+        let text_size = render::menu_text_size(ctx);
+        render::queue_centered(
+            ctx,
+            "GAME OVER",
+            center - Vec2::new(0.0, text_size * 7.0),
+            text_size * 3.0,
+            Color::WHITE,
+        );
+        render::queue_centered(
+            ctx,
+            "space to continue, escape for title",
+            center - Vec2::new(0.0, text_size * 4.0),
+            text_size,
+            Color::WHITE,
+        );
+        render::queue_centered(
+            ctx,
+            &format!("seed {}", self.seed),
+            center - Vec2::new(0.0, text_size * 2.75),
+            text_size,
+            Color::from_rgb_u32(0xbfbfbf),
+        );
+        if let Some(goal) = &self.goal {
+            render::queue_centered(
+                ctx,
+                goal,
+                center - Vec2::new(0.0, text_size * 1.5),
+                text_size,
+                Color::from_rgb_u32(0xbfbfbf),
+            );
+        } else if let GameKind::Free = self.kind {
+            self.queue_high_scores(
+                ctx,
+                shared,
+                center - Vec2::new(0.0, text_size * 1.5),
+                text_size * 0.8,
+            );
+        }
+        Ok(())
+    }
+
+    fn key_down(
+        &mut self,
+        ctx: &mut Context,
+        shared: &mut Shared,
+        keycode: KeyCode,
+    ) -> Transition {
+        match keycode {
+            KeyCode::Space => Transition::Reset(Box::new(
+                Playing::restart(ctx, shared, &self.kind),
+            )),
+            KeyCode::Escape => Transition::Home,
+            _ => Transition::None,
+        }
+    }
+
+    fn is_overlay(&self) -> bool {
+        true
+    }
+}
@@ -0,0 +1,57 @@
+use crate::render;
+
+use ggez::Context;
+use ggez::event::KeyCode;
+use ggez::graphics::Color;
+
+use glam::Vec2;
+
+/// A vertical list of items, one of them selected.
+pub struct Menu {
+    pub items: Vec<String>,
+    pub selected: usize,
+}
+
+impl Menu {
+    pub fn new(items: &[&str]) -> Self {
+        Self {
+            items: items.iter().map(|item| item.to_string()).collect(),
+            selected: 0,
+        }
+    }
+
+    /// Moves the selection. Returns the selected index when it is chosen.
+    pub fn key_down(&mut self, keycode: KeyCode) -> Option<usize> {
+        match keycode {
+            KeyCode::Up | KeyCode::W => {
+                self.selected = self.selected.checked_sub(1)
+                    .unwrap_or(self.items.len() - 1);
+                None
+            }
+            KeyCode::Down | KeyCode::S => {
+                self.selected = (self.selected + 1) % self.items.len();
+                None
+            }
+            KeyCode::Return | KeyCode::Space => Some(self.selected),
+            _ => None,
+        }
+    }
+
+    /// Queues the items in a column centred on `top.x`, starting at `top.y`.
+    pub fn queue(&self, ctx: &mut Context, top: Vec2, text_size: f32) {
+        for (i, item) in self.items.iter().enumerate() {
+            let (text, color) = if i == self.selected {
+                (format!("> {} <", item), Color::WHITE)
+            } else {
+                (item.clone(), Color::from_rgb_u32(0xbfbfbf))
+            };
+            render::queue_centered(
+                ctx,
+                &text,
+                top + Vec2::new(0.0, i as f32 * text_size * 1.5),
+                text_size,
+                color,
+            );
+        }
+    }
+}
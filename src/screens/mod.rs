@@ -0,0 +1,202 @@
+use crate::campaign::{Campaign, Progress};
+use crate::config::Config;
+use crate::scores::HighScores;
+
+use ggez::{Context, GameResult};
+use ggez::event::{self, KeyCode, MouseButton};
+use ggez::graphics;
+
+use glam::Vec2;
+
+mod campaign_select;
+mod editor;
+mod game_over;
+mod menu;
+mod mode_select;
+mod online;
+mod paused;
+mod playing;
+mod settings;
+mod title;
+mod versus;
+mod versus_over;
+mod victory;
+
+pub use editor::Editor;
+pub use online::OnlinePlaying;
+pub use playing::Playing;
+pub use title::Title;
+
+/// State that outlives any one screen.
+pub struct Shared {
+    pub config: Config,
+    pub fixed_seed: Option<u64>,
+    /// Whether new games start with the autopilot playing.
+    pub autopilot: bool,
+    pub high_scores: HighScores,
+    /// Loaded when the campaign is opened, so edits to its levels show up
+    /// without a restart.
+    pub campaign: Option<Campaign>,
+    pub progress: Progress,
+}
+
+/// What the screen stack should do after a screen has handled an event.
+pub enum Transition {
+    None,
+    Push(Box<dyn Screen>),
+    Pop,
+    /// Pops this screen and the one beneath it, such as a pause overlay
+    /// along with the game it paused.
+    PopTwo,
+    /// Pops back to the bottom screen, then pushes a new screen on it.
+    Reset(Box<dyn Screen>),
+    /// Pops back to the bottom screen.
+    Home,
+    Quit,
+}
+
+/// One screen of the game. Only the top screen of the stack receives
+/// updates and input.
+pub trait Screen {
+    fn update(&mut self, ctx: &mut Context, shared: &mut Shared) -> Transition;
+
+    fn draw(&mut self, ctx: &mut Context, shared: &Shared) -> GameResult<()>;
+
+    fn key_down(
+        &mut self,
+        _ctx: &mut Context,
+        _shared: &mut Shared,
+        _keycode: KeyCode,
+    ) -> Transition {
+        Transition::None
+    }
+
+    /// Called when a mouse button is pressed at `pos`, in screen pixels.
+    fn mouse_down(
+        &mut self,
+        _ctx: &mut Context,
+        _shared: &mut Shared,
+        _button: MouseButton,
+        _pos: Vec2,
+    ) -> Transition {
+        Transition::None
+    }
+
+    fn mouse_motion(&mut self, _ctx: &mut Context, _pos: Vec2) {}
+
+    fn focus(&mut self, _shared: &mut Shared, _gained: bool) -> Transition {
+        Transition::None
+    }
+
+    /// Called after the window changes size, so layouts can be recomputed.
+    fn resize(&mut self, _ctx: &mut Context) {}
+
+    /// Whether the screen underneath stays visible. Overlays are drawn on
+    /// top of it.
+    fn is_overlay(&self) -> bool {
+        false
+    }
+}
+
+pub struct ScreenStack {
+    screens: Vec<Box<dyn Screen>>,
+}
+
+impl ScreenStack {
+    pub fn new(bottom: Box<dyn Screen>) -> Self {
+        Self {
+            screens: vec![bottom],
+        }
+    }
+
+    pub fn push(&mut self, screen: Box<dyn Screen>) {
+        self.screens.push(screen);
+    }
+
+    fn top(&mut self) -> &mut Box<dyn Screen> {
+        self.screens.last_mut().expect("screen stack is never empty")
+    }
+
+    fn apply(&mut self, ctx: &mut Context, transition: Transition) {
+        match transition {
+            Transition::None => (),
+            Transition::Push(screen) => self.screens.push(screen),
+            Transition::Pop if self.screens.len() > 1 => {
+                self.screens.pop();
+            }
+            Transition::PopTwo if self.screens.len() > 2 => {
+                self.screens.truncate(self.screens.len() - 2);
+            }
+            Transition::Pop | Transition::PopTwo | Transition::Quit => {
+                event::quit(ctx)
+            }
+            Transition::Reset(screen) => {
+                self.screens.truncate(1);
+                self.screens.push(screen);
+            }
+            Transition::Home => self.screens.truncate(1),
+        }
+    }
+
+    pub fn update(&mut self, ctx: &mut Context, shared: &mut Shared) {
+        let transition = self.top().update(ctx, shared);
+        self.apply(ctx, transition);
+    }
+
+    /// Draws the top screen, and for overlays every screen beneath down to
+    /// the first opaque one. Each screen's text is flushed before the next
+    /// screen draws so overlays cover it.
+    pub fn draw(&mut self, ctx: &mut Context, shared: &Shared) -> GameResult<()> {
+        let first_visible = self.screens.iter()
+            .rposition(|screen| !screen.is_overlay())
+            .unwrap_or(0);
+        for screen in &mut self.screens[first_visible..] {
+            screen.draw(ctx, shared)?;
+            graphics::draw_queued_text(
+                ctx,
+                graphics::DrawParam::default(),
+                None,
+                graphics::FilterMode::Linear,
+            )?;
+        }
+        Ok(())
+    }
+
+    pub fn key_down(
+        &mut self,
+        ctx: &mut Context,
+        shared: &mut Shared,
+        keycode: KeyCode,
+    ) {
+        let transition = self.top().key_down(ctx, shared, keycode);
+        self.apply(ctx, transition);
+    }
+
+    pub fn mouse_down(
+        &mut self,
+        ctx: &mut Context,
+        shared: &mut Shared,
+        button: MouseButton,
+        pos: Vec2,
+    ) {
+        let transition = self.top().mouse_down(ctx, shared, button, pos);
+        self.apply(ctx, transition);
+    }
+
+    pub fn mouse_motion(&mut self, ctx: &mut Context, pos: Vec2) {
+        self.top().mouse_motion(ctx, pos);
+    }
+
+    /// Tells every screen, not just the top one, since overlays draw the
+    /// screens beneath them.
+    pub fn resize(&mut self, ctx: &mut Context) {
+        for screen in &mut self.screens {
+            screen.resize(ctx);
+        }
+    }
+
+    pub fn focus(&mut self, ctx: &mut Context, shared: &mut Shared, gained: bool) {
+        let transition = self.top().focus(shared, gained);
+        self.apply(ctx, transition);
+    }
+}
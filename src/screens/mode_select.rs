@@ -0,0 +1,139 @@
+use super::campaign_select::CampaignSelect;
+use super::menu::Menu;
+use super::versus::VersusPlaying;
+use super::{Playing, Screen, Shared, Transition};
+use crate::campaign::{self, Campaign};
+use crate::render;
+use crate::replay::{Playback, Replay};
+
+use ggez::{Context, GameResult};
+use ggez::event::KeyCode;
+use ggez::graphics::Color;
+
+use glam::Vec2;
+
+use std::path::Path;
+
+const CLASSIC: usize = 0;
+const WRAP_AROUND: usize = 1;
+const VERSUS: usize = 2;
+const CAMPAIGN: usize = 3;
+const WATCH_REPLAY: usize = 4;
+const BACK: usize = 5;
+
+pub struct ModeSelect {
+    menu: Menu,
+    message: Option<String>,
+}
+
+impl ModeSelect {
+    pub fn new() -> Self {
+        Self {
+            menu: Menu::new(&[
+                "Classic",
+                "Wrap-around",
+                "Versus",
+                "Campaign",
+                "Watch latest replay",
+                "Back",
+            ]),
+            message: None,
+        }
+    }
+
+    /// Loads the campaign afresh and opens its level list.
+    fn open_campaign(&mut self, shared: &mut Shared) -> Transition {
+        let path = Path::new(campaign::CAMPAIGN_PATH);
+        match Campaign::load(path) {
+            Ok(campaign) => {
+                let select = CampaignSelect::new(&campaign, shared);
+                shared.campaign = Some(campaign);
+                Transition::Push(Box::new(select))
+            }
+            Err(err) => {
+                self.message = Some(format!("{}: {}", path.display(), err));
+                Transition::None
+            }
+        }
+    }
+
+    fn watch_latest_replay(&mut self, ctx: &mut Context) -> Transition {
+        let path = match Replay::latest() {
+            Some(path) => path,
+            None => {
+                self.message = Some("no replays saved yet".to_string());
+                return Transition::None;
+            }
+        };
+        match Replay::load(&path) {
+            Ok(replay) => Transition::Reset(Box::new(
+                Playing::playback(ctx, Playback::new(replay)),
+            )),
+            Err(err) => {
+                self.message = Some(format!("{}: {}", path.display(), err));
+                Transition::None
+            }
+        }
+    }
+}
+
+impl Screen for ModeSelect {
+    fn update(&mut self, _ctx: &mut Context, _shared: &mut Shared) -> Transition {
+        Transition::None
+    }
+
+    fn draw(&mut self, ctx: &mut Context, _shared: &Shared) -> GameResult<()> {
+        let center = render::screen_center(ctx);
+        let text_size = render::menu_text_size(ctx);
+        render::queue_centered(
+            ctx,
+            "MODES",
+            center - Vec2::new(0.0, text_size * 4.0),
+            text_size * 2.0,
+            Color::WHITE,
+        );
+        self.menu.queue(ctx, center, text_size);
+        if let Some(message) = &self.message {
+            render::queue_centered(
+                ctx,
+                message,
+                center + Vec2::new(0.0, text_size * 9.0),
+                text_size * 0.75,
+                Color::RED,
+            );
+        }
+        Ok(())
+    }
+
+    fn key_down(
+        &mut self,
+        ctx: &mut Context,
+        shared: &mut Shared,
+        keycode: KeyCode,
+    ) -> Transition {
+        if keycode == KeyCode::Escape {
+            return Transition::Pop;
+        }
+        match self.menu.key_down(keycode) {
+            // The mode sticks to the shared config, so restarting from the
+            // game over screen keeps it.
+            Some(mode @ (CLASSIC | WRAP_AROUND)) => {
+                shared.config.wrap = mode == WRAP_AROUND;
+                Transition::Reset(Box::new(Playing::new(ctx, shared)))
+            }
+            Some(VERSUS) => match shared.config.validate_versus() {
+                Ok(()) => Transition::Reset(Box::new(
+                    VersusPlaying::new(ctx, shared),
+                )),
+                Err(err) => {
+                    self.message = Some(format!("versus: {}", err));
+                    Transition::None
+                }
+            },
+            Some(CAMPAIGN) => self.open_campaign(shared),
+            Some(WATCH_REPLAY) => self.watch_latest_replay(ctx),
+            Some(BACK) => Transition::Pop,
+            _ => Transition::None,
+        }
+    }
+}
@@ -0,0 +1,128 @@
+use super::playing::{arrow_direction, wasd_direction};
+use super::versus_over::VersusOver;
+use super::{Screen, Shared, Transition};
+use crate::netplay::Session;
+use crate::render::{self, BoardMesh, GeoConfig};
+use crate::simulation::Direction;
+
+use ggez::{Context, GameResult};
+use ggez::event::KeyCode;
+use ggez::graphics::Color;
+use ggez::timer;
+
+use std::collections::VecDeque;
+
+/// A versus round against a player on another machine. The local player
+/// steers with WASD or the arrow keys. There is no pausing, since the
+/// other side would have to wait.
+pub struct OnlinePlaying {
+    geo_config: GeoConfig,
+    board: BoardMesh,
+    session: Session,
+    /// Turns pressed but not yet sent, sent one per tick.
+    pending: VecDeque<Direction>,
+    /// Whether the last tick could not run for want of the other side.
+    waiting: bool,
+    /// Why the round was cut short, if it was.
+    error: Option<String>,
+}
+
+impl OnlinePlaying {
+    pub fn new(ctx: &mut Context, session: Session) -> Self {
+        let config = &session.versus().config;
+        // Drop ticks that built up while connecting.
+        while timer::check_update_time(ctx, config.target_fps) {}
+        Self {
+            geo_config: GeoConfig::new(ctx, config.dimensions),
+            board: BoardMesh::default(),
+            session,
+            pending: VecDeque::new(),
+            waiting: false,
+            error: None,
+        }
+    }
+}
+
+impl Screen for OnlinePlaying {
+    fn update(&mut self, ctx: &mut Context, _shared: &mut Shared) -> Transition {
+        let target_fps = self.session.versus().config.target_fps;
+        while timer::check_update_time(ctx, target_fps) {
+            if self.error.is_some() || self.session.result().is_some() {
+                continue;
+            }
+            let input = self.pending.pop_front();
+            let advanced = self.session.advance(input);
+            self.waiting = matches!(advanced, Ok(None));
+            match advanced {
+                // A rollback can change the board on any tick.
+                Ok(Some(_)) => self.board.invalidate(),
+                // Keep the turn for when the tick can run.
+                Ok(None) => {
+                    if let Some(direction) = input {
+                        self.pending.push_front(direction);
+                    }
+                }
+                Err(err) => self.error = Some(err.to_string()),
+            }
+        }
+        if self.session.result().is_some() {
+            self.board.invalidate();
+            let versus = self.session.versus();
+            Transition::Push(Box::new(VersusOver::new(versus, false)))
+        } else {
+            Transition::None
+        }
+    }
+
+    fn draw(&mut self, ctx: &mut Context, _shared: &Shared) -> GameResult<()> {
+        let versus = self.session.versus();
+        self.board.draw_versus(ctx, &self.geo_config, versus)?;
+        let local = self.session.local();
+        let status = if self.waiting {
+            format!("online as player {}, waiting for peer", local + 1)
+        } else if versus.players[local].started() {
+            format!("online as player {}", local + 1)
+        } else {
+            format!("online as player {}, pick a direction", local + 1)
+        };
+        render::queue_status(
+            ctx,
+            &self.geo_config,
+            versus.config.dimensions,
+            &status,
+        );
+        if let Some(error) = &self.error {
+            render::queue_centered(
+                ctx,
+                &format!("{} - escape for title", error),
+                render::screen_center(ctx),
+                render::menu_text_size(ctx),
+                Color::RED,
+            );
+        }
+        Ok(())
+    }
+
+    fn key_down(
+        &mut self,
+        _ctx: &mut Context,
+        _shared: &mut Shared,
+        keycode: KeyCode,
+    ) -> Transition {
+        if keycode == KeyCode::Escape {
+            return Transition::Home;
+        }
+        let direction = wasd_direction(keycode)
+            .or_else(|| arrow_direction(keycode));
+        if let Some(direction) = direction {
+            self.pending.push_back(direction);
+        }
+        Transition::None
+    }
+
+    fn resize(&mut self, ctx: &mut Context) {
+        let dimensions = self.session.versus().config.dimensions;
+        self.geo_config = GeoConfig::new(ctx, dimensions);
+        self.board.invalidate();
+    }
+}
@@ -0,0 +1,150 @@
+use super::{Screen, Shared, Transition};
+use crate::render;
+
+use ggez::{Context, GameResult};
+use ggez::event::KeyCode;
+use ggez::graphics::{self, Color};
+use ggez::timer;
+
+use glam::Vec2;
+
+const RESUME_COUNTDOWN_SECS: u32 = 3;
+
+/// Drawn over a paused game. Resuming counts down first so the player is
+/// not killed the moment play restarts.
+pub struct Paused {
+    state: PauseState,
+    /// The paused game's frame rate, which the countdown runs at.
+    target_fps: u32,
+    /// Set when the paused game is a test play from the level editor,
+    /// which quitting goes back to.
+    test_play: bool,
+}
+
+#[derive(Copy, Clone, PartialEq)]
+enum PauseState {
+    /// Paused by the player, or automatically when the window lost focus.
+    Waiting { auto: bool },
+    /// Resuming once `frames` runs out.
+    Countdown { frames: u32 },
+}
+
+impl Paused {
+    pub fn new(auto: bool, target_fps: u32) -> Self {
+        Self {
+            state: PauseState::Waiting { auto },
+            target_fps,
+            test_play: false,
+        }
+    }
+
+    /// Pauses a test play, so quitting goes back to the editor rather than
+    /// to the title screen.
+    pub fn test_play(auto: bool, target_fps: u32) -> Self {
+        Self {
+            test_play: true,
+            ..Self::new(auto, target_fps)
+        }
+    }
+
+    fn resume(&mut self) {
+        self.state = PauseState::Countdown {
+            frames: RESUME_COUNTDOWN_SECS * self.target_fps,
+        };
+    }
+}
+
+impl Screen for Paused {
+    /// Counts down towards resuming. Ticks are consumed either way so the
+    /// game does not try to catch up on the time spent paused.
+    fn update(&mut self, ctx: &mut Context, _shared: &mut Shared) -> Transition {
+        while timer::check_update_time(ctx, self.target_fps) {
+            if let PauseState::Countdown { frames } = &mut self.state {
+                *frames -= 1;
+                if *frames == 0 {
+                    return Transition::Pop;
+                }
+            }
+        }
+        Transition::None
+    }
+
+    fn draw(&mut self, ctx: &mut Context, _shared: &Shared) -> GameResult<()> {
+        let (width, height) = graphics::drawable_size(ctx);
+        let shade = graphics::Mesh::new_rectangle(
+            ctx,
+            graphics::DrawMode::Fill(graphics::FillOptions::default()),
+            graphics::Rect::new(0.0, 0.0, width, height),
+            Color::new(0.0, 0.0, 0.0, 0.5),
+        )?;
+        graphics::draw(ctx, &shade, graphics::DrawParam::default())?;
+
+        let (title, subtitle) = match self.state {
+            PauseState::Waiting { .. } if self.test_play => (
+                "PAUSED".to_string(),
+                "p to resume, escape to go back to the editor",
+            ),
+            PauseState::Waiting { .. } => (
+                "PAUSED".to_string(),
+                "p to resume, escape to quit to title",
+            ),
+            PauseState::Countdown { frames } => (
+                frames.div_ceil(self.target_fps).to_string(),
+                "get ready",
+            ),
+        };
+        let center = render::screen_center(ctx);
+        let text_size = render::menu_text_size(ctx);
+        render::queue_centered(
+            ctx,
+            &title,
+            center - Vec2::new(0.0, text_size * 2.0),
+            text_size * 3.0,
+            Color::WHITE,
+        );
+        render::queue_centered(
+            ctx,
+            subtitle,
+            center + Vec2::new(0.0, text_size),
+            text_size,
+            Color::WHITE,
+        );
+        Ok(())
+    }
+
+    fn key_down(
+        &mut self,
+        _ctx: &mut Context,
+        _shared: &mut Shared,
+        keycode: KeyCode,
+    ) -> Transition {
+        match (keycode, self.state) {
+            (KeyCode::Escape, _) if self.test_play => Transition::PopTwo,
+            (KeyCode::Escape, _) => Transition::Home,
+            (KeyCode::P, PauseState::Waiting { .. }) => {
+                self.resume();
+                Transition::None
+            }
+            (KeyCode::P, PauseState::Countdown { .. }) => {
+                self.state = PauseState::Waiting { auto: false };
+                Transition::None
+            }
+            _ => Transition::None,
+        }
+    }
+
+    fn focus(&mut self, _shared: &mut Shared, gained: bool) -> Transition {
+        match (gained, self.state) {
+            (false, PauseState::Countdown { .. }) => {
+                self.state = PauseState::Waiting { auto: true };
+            }
+            (true, PauseState::Waiting { auto: true }) => self.resume(),
+            _ => (),
+        }
+        Transition::None
+    }
+
+    fn is_overlay(&self) -> bool {
+        true
+    }
+}
@@ -0,0 +1,379 @@
+use super::game_over::GameOver;
+use super::paused::Paused;
+use super::victory::Victory;
+use super::{Screen, Shared, Transition};
+use crate::autopilot::Autopilot;
+use crate::config::Config;
+use crate::render::{self, BoardMesh, GeoConfig};
+use crate::replay::{Playback, Replay};
+use crate::scores::ScoreEntry;
+use crate::simulation::{Direction, Simulation, StepOutcome};
+
+use ggez::{Context, GameResult};
+use ggez::event::KeyCode;
+use ggez::timer;
+
+/// What kind of run a game is, so that it can be started again.
+#[derive(Clone)]
+pub enum GameKind {
+    /// A new game with the shared config.
+    Free,
+    /// Watching a recorded run.
+    Replay(Replay),
+    /// A campaign level, by its index in the loaded campaign.
+    Campaign(usize),
+}
+
+pub struct Playing {
+    geo_config: GeoConfig,
+    board: BoardMesh,
+    sim: Simulation,
+    playback: Option<Playback>,
+    autopilot: Option<Autopilot>,
+    /// Set once the autopilot has played any part of the run, which keeps
+    /// it off the high-score table.
+    assisted: bool,
+    /// Set for a level being tried out from the editor.
+    test_play: bool,
+    campaign_level: Option<usize>,
+}
+
+/// The direction a WASD key steers in.
+pub fn wasd_direction(keycode: KeyCode) -> Option<Direction> {
+    match keycode {
+        KeyCode::W => Some(Direction::UP),
+        KeyCode::S => Some(Direction::DOWN),
+        KeyCode::A => Some(Direction::LEFT),
+        KeyCode::D => Some(Direction::RIGHT),
+        _ => None,
+    }
+}
+
+/// The direction an arrow key steers in.
+pub fn arrow_direction(keycode: KeyCode) -> Option<Direction> {
+    match keycode {
+        KeyCode::Up => Some(Direction::UP),
+        KeyCode::Down => Some(Direction::DOWN),
+        KeyCode::Left => Some(Direction::LEFT),
+        KeyCode::Right => Some(Direction::RIGHT),
+        _ => None,
+    }
+}
+
+/// The direction a key steers the snake in. Arrows and WASD both work.
+fn key_direction(keycode: KeyCode) -> Option<Direction> {
+    wasd_direction(keycode).or_else(|| arrow_direction(keycode))
+}
+
+impl Playing {
+    /// Starts a new game with the shared config.
+    pub fn new(ctx: &mut Context, shared: &Shared) -> Self {
+        let seed = shared.fixed_seed.unwrap_or_else(rand::random);
+        let mut playing = Self::with_simulation(
+            ctx,
+            Simulation::new(seed, shared.config.clone()),
+            None,
+        );
+        if shared.autopilot {
+            playing.toggle_autopilot();
+        }
+        playing
+    }
+
+    /// Test-plays a level from the editor. The run is not recorded, and
+    /// ending it goes straight back to the editor.
+    pub fn test_level(
+        ctx: &mut Context,
+        shared: &Shared,
+        config: Config,
+    ) -> Self {
+        let seed = shared.fixed_seed.unwrap_or_else(rand::random);
+        let mut playing = Self::with_simulation(
+            ctx,
+            Simulation::new(seed, config),
+            None,
+        );
+        playing.test_play = true;
+        playing
+    }
+
+    /// Plays level `index` of the loaded campaign.
+    pub fn campaign(ctx: &mut Context, shared: &Shared, index: usize) -> Self {
+        let campaign = shared.campaign.as_ref()
+            .expect("the campaign is loaded before its levels are played");
+        let seed = shared.fixed_seed.unwrap_or_else(rand::random);
+        let mut playing = Self::with_simulation(
+            ctx,
+            Simulation::new(seed, campaign.levels[index].config.clone()),
+            None,
+        );
+        playing.campaign_level = Some(index);
+        if shared.autopilot {
+            playing.toggle_autopilot();
+        }
+        playing
+    }
+
+    /// Plays back a recorded run with its own seed and config.
+    pub fn playback(ctx: &mut Context, playback: Playback) -> Self {
+        let sim = Simulation::new(
+            playback.replay.seed,
+            playback.replay.config.clone(),
+        );
+        Self::with_simulation(ctx, sim, Some(playback))
+    }
+
+    /// Starts the same kind of game again: a new game, the same replay
+    /// from the beginning, or the same level.
+    pub fn restart(
+        ctx: &mut Context,
+        shared: &Shared,
+        kind: &GameKind,
+    ) -> Self {
+        match kind {
+            GameKind::Free => Self::new(ctx, shared),
+            GameKind::Replay(replay) => {
+                Self::playback(ctx, Playback::new(replay.clone()))
+            }
+            GameKind::Campaign(index) => Self::campaign(ctx, shared, *index),
+        }
+    }
+
+    fn with_simulation(
+        ctx: &mut Context,
+        sim: Simulation,
+        playback: Option<Playback>,
+    ) -> Self {
+        // Drop ticks that built up while no game was running, so the snake
+        // does not jump ahead on the first update.
+        while timer::check_update_time(ctx, sim.config.target_fps) {}
+        Self {
+            geo_config: GeoConfig::new(ctx, sim.config.dimensions),
+            board: BoardMesh::default(),
+            sim,
+            playback,
+            autopilot: None,
+            assisted: false,
+            test_play: false,
+            campaign_level: None,
+        }
+    }
+
+    fn toggle_autopilot(&mut self) {
+        if self.autopilot.take().is_none() {
+            self.autopilot = Some(Autopilot::new());
+            self.assisted = true;
+        }
+    }
+
+    /// Pauses over the game, `auto` when the window lost focus.
+    fn pause(&self, auto: bool) -> Transition {
+        let target_fps = self.sim.config.target_fps;
+        let paused = if self.test_play {
+            Paused::test_play(auto, target_fps)
+        } else {
+            Paused::new(auto, target_fps)
+        };
+        Transition::Push(Box::new(paused))
+    }
+
+    /// Saves the finished run and shows the game over or victory screen
+    /// over it.
+    fn finish(&mut self, shared: &mut Shared) -> Transition {
+        if self.test_play {
+            return Transition::Pop;
+        }
+        if let Some(playback) = &self.playback {
+            if !playback.matches(&self.sim) {
+                eprintln!(
+                    "replay diverged: ended at tick {} with score {}, \
+                     recorded tick {} with score {}",
+                    self.sim.tick,
+                    self.sim.score,
+                    playback.replay.ticks,
+                    playback.replay.score,
+                );
+            }
+            let kind = GameKind::Replay(playback.replay.clone());
+            if self.sim.won {
+                return Transition::Push(Box::new(Victory::new(
+                    &self.sim,
+                    kind,
+                    None,
+                )));
+            }
+            return Transition::Push(Box::new(GameOver::new(
+                &self.sim,
+                kind,
+                None,
+                false,
+            )));
+        }
+
+        match Replay::from_simulation(&self.sim).save() {
+            Ok(path) => println!("replay saved to {}", path.display()),
+            Err(err) => eprintln!("failed to save replay: {}", err),
+        }
+        if let Some(index) = self.campaign_level {
+            return self.finish_level(shared, index);
+        }
+        if self.assisted {
+            if self.sim.won {
+                return Transition::Push(Box::new(Victory::new(
+                    &self.sim,
+                    GameKind::Free,
+                    None,
+                )));
+            }
+            return Transition::Push(Box::new(GameOver::new(
+                &self.sim,
+                GameKind::Free,
+                None,
+                false,
+            )));
+        }
+        let entry = ScoreEntry::from_simulation(&self.sim);
+        // Only a full board counts as a win on record. A level played on
+        // its own whose goal is met goes on the score table as usual.
+        if self.sim.won && self.sim.config.goal.is_none() {
+            shared.high_scores.record_win(entry);
+            if let Err(err) = shared.high_scores.save() {
+                eprintln!("failed to save high scores: {}", err);
+            }
+            return Transition::Push(Box::new(Victory::new(
+                &self.sim,
+                GameKind::Free,
+                Some(shared.high_scores.wins.len()),
+            )));
+        }
+        let new_best = match shared.high_scores.best() {
+            Some(best) => entry.score > best,
+            None => entry.score > 0,
+        };
+        let new_rank = shared.high_scores.insert(entry);
+        if new_rank.is_some() {
+            if let Err(err) = shared.high_scores.save() {
+                eprintln!("failed to save high scores: {}", err);
+            }
+        }
+        if self.sim.won {
+            return Transition::Push(Box::new(Victory::new(
+                &self.sim,
+                GameKind::Free,
+                None,
+            )));
+        }
+        Transition::Push(Box::new(GameOver::new(
+            &self.sim,
+            GameKind::Free,
+            new_rank,
+            new_best,
+        )))
+    }
+
+    /// Ends a campaign level. Meeting the goal clears the level, unlocking
+    /// the next one, unless the autopilot helped. Campaign runs stay off
+    /// the high-score table.
+    fn finish_level(&self, shared: &mut Shared, index: usize) -> Transition {
+        let kind = GameKind::Campaign(index);
+        if !self.sim.won {
+            return Transition::Push(Box::new(GameOver::new(
+                &self.sim,
+                kind,
+                None,
+                false,
+            )));
+        }
+        let campaign = shared.campaign.as_ref()
+            .expect("the campaign is loaded while its levels are played");
+        let name = &campaign.levels[index].name;
+        if !self.assisted && shared.progress.record(name) {
+            if let Err(err) = shared.progress.save() {
+                eprintln!("failed to save campaign progress: {}", err);
+            }
+        }
+        Transition::Push(Box::new(Victory::new(&self.sim, kind, None)))
+    }
+}
+
+impl Screen for Playing {
+    fn update(&mut self, ctx: &mut Context, shared: &mut Shared) -> Transition {
+        while timer::check_update_time(ctx, self.sim.config.target_fps) {
+            let input = match (&mut self.playback, &mut self.autopilot) {
+                (Some(playback), _) => playback.next_input(&self.sim),
+                (None, Some(autopilot)) => autopilot.next_input(&self.sim),
+                (None, None) => None,
+            };
+            let outcome = self.sim.step(input);
+            if !matches!(outcome, StepOutcome::Idle | StepOutcome::Waiting) {
+                self.board.invalidate();
+            }
+        }
+        if self.sim.live {
+            Transition::None
+        } else {
+            self.finish(shared)
+        }
+    }
+
+    fn draw(&mut self, ctx: &mut Context, _shared: &Shared) -> GameResult<()> {
+        self.board.draw(ctx, &self.geo_config, &self.sim)?;
+        let mut status = Vec::new();
+        if let (Some(goal), Some(progress)) =
+            (self.sim.config.goal, self.sim.goal_progress())
+        {
+            let target = goal.target;
+            status.push(format!("{} {}/{}", goal.name(), progress, target));
+        }
+        if self.autopilot.is_some() {
+            status.push("autopilot (tab)".to_string());
+        }
+        if !status.is_empty() {
+            render::queue_status(
+                ctx,
+                &self.geo_config,
+                self.sim.config.dimensions,
+                &status.join("   "),
+            );
+        }
+        Ok(())
+    }
+
+    fn key_down(
+        &mut self,
+        _ctx: &mut Context,
+        _shared: &mut Shared,
+        keycode: KeyCode,
+    ) -> Transition {
+        if self.test_play && keycode == KeyCode::Escape {
+            return Transition::Pop;
+        }
+        if let KeyCode::P | KeyCode::Escape = keycode {
+            return self.pause(false);
+        }
+        if self.playback.is_some() {
+            return Transition::None;
+        }
+        if keycode == KeyCode::Tab {
+            self.toggle_autopilot();
+        } else if let (Some(direction), None) =
+            (key_direction(keycode), &self.autopilot)
+        {
+            self.sim.turn(direction);
+        }
+        Transition::None
+    }
+
+    fn resize(&mut self, ctx: &mut Context) {
+        self.geo_config = GeoConfig::new(ctx, self.sim.config.dimensions);
+        self.board.invalidate();
+    }
+
+    fn focus(&mut self, _shared: &mut Shared, gained: bool) -> Transition {
+        if gained {
+            Transition::None
+        } else {
+            self.pause(true)
+        }
+    }
+}
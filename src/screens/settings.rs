@@ -0,0 +1,150 @@
+use super::menu::Menu;
+use super::{Screen, Shared, Transition};
+use crate::config::Config;
+use crate::render;
+
+use ggez::{Context, GameResult};
+use ggez::event::KeyCode;
+use ggez::graphics::Color;
+
+use glam::{IVec2, Vec2};
+
+const BOARD_SIZES: [IVec2; 4] = [
+    glam::const_ivec2!([32, 18]),
+    glam::const_ivec2!([48, 27]),
+    glam::const_ivec2!([76, 45]),
+    glam::const_ivec2!([96, 54]),
+];
+const FRAME_DELAYS: [u8; 6] = [8, 7, 6, 5, 4, 3];
+const WALL_FRAMES: [u32; 4] = [1080, 720, 360, 180];
+
+const BOARD_SIZE: usize = 0;
+const SPEED: usize = 1;
+const WALLS: usize = 2;
+const BACK: usize = 3;
+
+/// Adjusts the config used by new games. Left and right step through the
+/// values of the selected setting.
+pub struct Settings {
+    menu: Menu,
+    message: Option<String>,
+}
+
+/// Steps from `current` to its neighbour in `options`. A value not in the
+/// list steps to the first or last option.
+fn step<T: Copy + PartialEq>(options: &[T], current: T, forward: bool) -> T {
+    let index = match options.iter().position(|&option| option == current) {
+        Some(index) if forward => (index + 1).min(options.len() - 1),
+        Some(index) => index.saturating_sub(1),
+        None if forward => options.len() - 1,
+        None => 0,
+    };
+    options[index]
+}
+
+impl Settings {
+    pub fn new(shared: &Shared) -> Self {
+        let mut settings = Self {
+            menu: Menu::new(&["", "", "", "Back"]),
+            message: None,
+        };
+        settings.relabel(&shared.config);
+        settings
+    }
+
+    fn relabel(&mut self, config: &Config) {
+        self.menu.items[BOARD_SIZE] = format!(
+            "Board size: {}x{}",
+            config.dimensions.x,
+            config.dimensions.y,
+        );
+        self.menu.items[SPEED] = format!(
+            "Starting speed: {}",
+            10 - config.starting_frame_delay as i32,
+        );
+        self.menu.items[WALLS] = format!(
+            "Stone wall every {:.1}s",
+            config.sw_gen_frames as f32 / config.target_fps as f32,
+        );
+    }
+
+    fn adjust(&mut self, shared: &mut Shared, forward: bool) {
+        let mut config = shared.config.clone();
+        match self.menu.selected {
+            BOARD_SIZE => {
+                config.dimensions = step(&BOARD_SIZES, config.dimensions, forward);
+            }
+            SPEED => {
+                config.starting_frame_delay =
+                    step(&FRAME_DELAYS, config.starting_frame_delay, forward);
+            }
+            WALLS => {
+                config.sw_gen_frames =
+                    step(&WALL_FRAMES, config.sw_gen_frames, forward);
+            }
+            _ => return,
+        }
+        match config.validate() {
+            Ok(()) => {
+                self.message = None;
+                self.relabel(&config);
+                shared.config = config;
+            }
+            Err(err) => self.message = Some(err.to_string()),
+        }
+    }
+}
+
+impl Screen for Settings {
+    fn update(&mut self, _ctx: &mut Context, _shared: &mut Shared) -> Transition {
+        Transition::None
+    }
+
+    fn draw(&mut self, ctx: &mut Context, _shared: &Shared) -> GameResult<()> {
+        let center = render::screen_center(ctx);
+        let text_size = render::menu_text_size(ctx);
+        render::queue_centered(
+            ctx,
+            "SETTINGS",
+            center - Vec2::new(0.0, text_size * 4.0),
+            text_size * 2.0,
+            Color::WHITE,
+        );
+        self.menu.queue(ctx, center, text_size);
+        render::queue_centered(
+            ctx,
+            "left / right to change",
+            center + Vec2::new(0.0, text_size * 7.0),
+            text_size * 0.75,
+            Color::from_rgb_u32(0xbfbfbf),
+        );
+        if let Some(message) = &self.message {
+            render::queue_centered(
+                ctx,
+                message,
+                center + Vec2::new(0.0, text_size * 8.0),
+                text_size * 0.75,
+                Color::RED,
+            );
+        }
+        Ok(())
+    }
+
+    fn key_down(
+        &mut self,
+        _ctx: &mut Context,
+        shared: &mut Shared,
+        keycode: KeyCode,
+    ) -> Transition {
+        match keycode {
+            KeyCode::Escape => return Transition::Pop,
+            KeyCode::Left | KeyCode::A => self.adjust(shared, false),
+            KeyCode::Right | KeyCode::D => self.adjust(shared, true),
+            _ => (),
+        }
+        match self.menu.key_down(keycode) {
+            Some(BACK) => Transition::Pop,
+            _ => Transition::None,
+        }
+    }
+}
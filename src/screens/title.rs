@@ -0,0 +1,100 @@
+use super::editor::{self, Editor};
+use super::menu::Menu;
+use super::mode_select::ModeSelect;
+use super::settings::Settings;
+use super::{Playing, Screen, Shared, Transition};
+use crate::render;
+
+use ggez::{Context, GameResult};
+use ggez::event::KeyCode;
+use ggez::graphics::Color;
+
+use glam::Vec2;
+
+use std::path::PathBuf;
+
+const PLAY: usize = 0;
+const MODES: usize = 1;
+const SETTINGS: usize = 2;
+const EDITOR: usize = 3;
+const QUIT: usize = 4;
+
+pub struct Title {
+    menu: Menu,
+}
+
+impl Title {
+    pub fn new() -> Self {
+        Self {
+            menu: Menu::new(&[
+                "Play",
+                "Modes",
+                "Settings",
+                "Level editor",
+                "Quit",
+            ]),
+        }
+    }
+}
+
+impl Screen for Title {
+    fn update(&mut self, _ctx: &mut Context, _shared: &mut Shared) -> Transition {
+        Transition::None
+    }
+
+    fn draw(&mut self, ctx: &mut Context, shared: &Shared) -> GameResult<()> {
+        let center = render::screen_center(ctx);
+        let text_size = render::menu_text_size(ctx);
+        render::queue_centered(
+            ctx,
+            "SNAKE",
+            center - Vec2::new(0.0, text_size * 6.0),
+            text_size * 3.0,
+            Color::GREEN,
+        );
+        if let Some(best) = shared.high_scores.best() {
+            render::queue_centered(
+                ctx,
+                &format!("best {}", best),
+                center - Vec2::new(0.0, text_size * 3.5),
+                text_size,
+                Color::from_rgb_u32(0xbfbfbf),
+            );
+        }
+        let wins = shared.high_scores.wins.len();
+        if wins > 0 {
+            render::queue_centered(
+                ctx,
+                &format!("boards cleared {}", wins),
+                center - Vec2::new(0.0, text_size * 2.25),
+                text_size * 0.8,
+                Color::YELLOW,
+            );
+        }
+        self.menu.queue(ctx, center, text_size);
+        Ok(())
+    }
+
+    fn key_down(
+        &mut self,
+        ctx: &mut Context,
+        shared: &mut Shared,
+        keycode: KeyCode,
+    ) -> Transition {
+        if keycode == KeyCode::Escape {
+            return Transition::Quit;
+        }
+        match self.menu.key_down(keycode) {
+            Some(PLAY) => Transition::Push(Box::new(Playing::new(ctx, shared))),
+            Some(MODES) => Transition::Push(Box::new(ModeSelect::new())),
+            Some(SETTINGS) => Transition::Push(Box::new(Settings::new(shared))),
+            Some(EDITOR) => Transition::Push(Box::new(Editor::open(
+                ctx,
+                shared,
+                PathBuf::from(editor::DEFAULT_LEVEL_PATH),
+            ))),
+            Some(QUIT) => Transition::Quit,
+            _ => Transition::None,
+        }
+    }
+}
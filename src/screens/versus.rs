@@ -0,0 +1,96 @@
+use super::paused::Paused;
+use super::playing::{arrow_direction, wasd_direction};
+use super::versus_over::VersusOver;
+use super::{Screen, Shared, Transition};
+use crate::render::{self, BoardMesh, GeoConfig};
+use crate::simulation::StepOutcome;
+use crate::versus::{Versus, PLAYERS};
+
+use ggez::{Context, GameResult};
+use ggez::event::KeyCode;
+use ggez::timer;
+
+/// A versus round for two players at one keyboard: the first steers with
+/// WASD and the second with the arrow keys.
+pub struct VersusPlaying {
+    geo_config: GeoConfig,
+    board: BoardMesh,
+    versus: Versus,
+}
+
+impl VersusPlaying {
+    /// Starts a round on the shared config's board.
+    pub fn new(ctx: &mut Context, shared: &Shared) -> Self {
+        let seed = shared.fixed_seed.unwrap_or_else(rand::random);
+        let versus = Versus::new(seed, shared.config.clone());
+        // Drop ticks that built up while no round was running.
+        while timer::check_update_time(ctx, versus.config.target_fps) {}
+        Self {
+            geo_config: GeoConfig::new(ctx, versus.config.dimensions),
+            board: BoardMesh::default(),
+            versus,
+        }
+    }
+}
+
+impl Screen for VersusPlaying {
+    fn update(&mut self, ctx: &mut Context, _shared: &mut Shared) -> Transition {
+        while timer::check_update_time(ctx, self.versus.config.target_fps) {
+            let outcome = self.versus.step([None; PLAYERS]);
+            if !matches!(outcome, StepOutcome::Idle | StepOutcome::Waiting) {
+                self.board.invalidate();
+            }
+        }
+        if self.versus.result.is_some() {
+            Transition::Push(Box::new(VersusOver::new(&self.versus, true)))
+        } else {
+            Transition::None
+        }
+    }
+
+    fn draw(&mut self, ctx: &mut Context, _shared: &Shared) -> GameResult<()> {
+        self.board.draw_versus(ctx, &self.geo_config, &self.versus)?;
+        if !self.versus.players.iter().all(|player| player.started()) {
+            render::queue_status(
+                ctx,
+                &self.geo_config,
+                self.versus.config.dimensions,
+                "both players pick a direction to start",
+            );
+        }
+        Ok(())
+    }
+
+    fn key_down(
+        &mut self,
+        _ctx: &mut Context,
+        _shared: &mut Shared,
+        keycode: KeyCode,
+    ) -> Transition {
+        if let KeyCode::P | KeyCode::Escape = keycode {
+            let target_fps = self.versus.config.target_fps;
+            return Transition::Push(Box::new(Paused::new(false, target_fps)));
+        }
+        let players = &mut self.versus.players;
+        if let Some(direction) = wasd_direction(keycode) {
+            players[0].turn(direction);
+        } else if let Some(direction) = arrow_direction(keycode) {
+            players[1].turn(direction);
+        }
+        Transition::None
+    }
+
+    fn resize(&mut self, ctx: &mut Context) {
+        self.geo_config = GeoConfig::new(ctx, self.versus.config.dimensions);
+        self.board.invalidate();
+    }
+
+    fn focus(&mut self, _shared: &mut Shared, gained: bool) -> Transition {
+        if gained {
+            Transition::None
+        } else {
+            let target_fps = self.versus.config.target_fps;
+            Transition::Push(Box::new(Paused::new(true, target_fps)))
+        }
+    }
+}
@@ -0,0 +1,111 @@
+use super::versus::VersusPlaying;
+use super::{Screen, Shared, Transition};
+use crate::render;
+use crate::versus::{Versus, VersusResult};
+
+use ggez::{Context, GameResult};
+use ggez::event::KeyCode;
+use ggez::graphics::Color;
+
+use glam::Vec2;
+
+/// Drawn over the board of a finished versus round.
+pub struct VersusOver {
+    result: VersusResult,
+    /// Whether Space starts a rematch on this machine. Online rounds
+    /// have none.
+    rematch: bool,
+    /// One line per player: their score and how their snake ended up.
+    lines: Vec<String>,
+}
+
+impl VersusOver {
+    pub fn new(versus: &Versus, rematch: bool) -> Self {
+        let lines = versus.players.iter()
+            .enumerate()
+            .map(|(index, player)| {
+                let ending = match player.death {
+                    Some(cause) => cause.name(),
+                    None => "alive",
+                };
+                format!(
+                    "player {}: score {}, {}",
+                    index + 1,
+                    player.score,
+                    ending,
+                )
+            })
+            .collect();
+        Self {
+            result: versus.result.expect("the round is over"),
+            rematch,
+            lines,
+        }
+    }
+}
+
+impl Screen for VersusOver {
+    fn update(&mut self, _ctx: &mut Context, _shared: &mut Shared) -> Transition {
+        Transition::None
+    }
+
+    fn draw(&mut self, ctx: &mut Context, _shared: &Shared) -> GameResult<()> {
+        let center = render::screen_center(ctx);
+        let text_size = render::menu_text_size(ctx);
+        let (title, color) = match self.result {
+            VersusResult::Winner(index) => (
+                format!("PLAYER {} WINS", index + 1),
+                render::player_color(index),
+            ),
+            VersusResult::Draw => ("DRAW".to_string(), Color::WHITE),
+        };
+        render::queue_centered(
+            ctx,
+            &title,
+            center - Vec2::new(0.0, text_size * 5.0),
+            text_size * 3.0,
+            color,
+        );
+        let hint = if self.rematch {
+            "space for a rematch, escape for title"
+        } else {
+            "escape for title"
+        };
+        render::queue_centered(
+            ctx,
+            hint,
+            center - Vec2::new(0.0, text_size * 2.0),
+            text_size,
+            Color::WHITE,
+        );
+        for (index, line) in self.lines.iter().enumerate() {
+            render::queue_centered(
+                ctx,
+                line,
+                center + Vec2::new(0.0, index as f32 * text_size * 1.2),
+                text_size,
+                render::player_color(index),
+            );
+        }
+        Ok(())
+    }
+
+    fn key_down(
+        &mut self,
+        ctx: &mut Context,
+        shared: &mut Shared,
+        keycode: KeyCode,
+    ) -> Transition {
+        match keycode {
+            KeyCode::Space if self.rematch => Transition::Reset(Box::new(
+                VersusPlaying::new(ctx, shared),
+            )),
+            KeyCode::Escape => Transition::Home,
+            _ => Transition::None,
+        }
+    }
+
+    fn is_overlay(&self) -> bool {
+        true
+    }
+}
@@ -0,0 +1,134 @@
+use super::playing::GameKind;
+use super::{Playing, Screen, Shared, Transition};
+use crate::config::Goal;
+use crate::render;
+use crate::scores::{self, ScoreEntry};
+use crate::simulation::Simulation;
+
+use ggez::{Context, GameResult};
+use ggez::event::KeyCode;
+use ggez::graphics::Color;
+
+use glam::Vec2;
+
+/// Drawn over the board of a run that filled it or met its goal.
+pub struct Victory {
+    /// The run as the score table would record it.
+    entry: ScoreEntry,
+    goal: Option<Goal>,
+    kind: GameKind,
+    /// How many wins are on record, counting this one.
+    wins: Option<usize>,
+}
+
+impl Victory {
+    pub fn new(
+        sim: &Simulation,
+        kind: GameKind,
+        wins: Option<usize>,
+    ) -> Self {
+        Self {
+            entry: ScoreEntry::from_simulation(sim),
+            goal: sim.config.goal,
+            kind,
+            wins,
+        }
+    }
+
+    /// The campaign level to go on to, if this was a campaign level and
+    /// the next one is unlocked.
+    fn next_level(&self, shared: &Shared) -> Option<usize> {
+        let (GameKind::Campaign(index), Some(campaign)) =
+            (&self.kind, &shared.campaign)
+        else {
+            return None;
+        };
+        let next = index + 1;
+        let unlocked = next < campaign.levels.len() &&
+            campaign.is_unlocked(&shared.progress, next);
+        unlocked.then_some(next)
+    }
+}
+
+impl Screen for Victory {
+    fn update(&mut self, _ctx: &mut Context, _shared: &mut Shared) -> Transition {
+        Transition::None
+    }
+
+    fn draw(&mut self, ctx: &mut Context, shared: &Shared) -> GameResult<()> {
+        let center = render::screen_center(ctx);
+        let text_size = render::menu_text_size(ctx);
+        let title = match (&self.kind, self.goal) {
+            (GameKind::Campaign(_), _) => "LEVEL COMPLETE",
+            (_, Some(_)) => "GOAL MET",
+            (_, None) => "BOARD CLEARED",
+        };
+        let hint = match self.next_level(shared) {
+            Some(_) => "space for the next level, escape for title",
+            None => "space to continue, escape for title",
+        };
+        render::queue_centered(
+            ctx,
+            title,
+            center - Vec2::new(0.0, text_size * 5.0),
+            text_size * 3.0,
+            Color::YELLOW,
+        );
+        render::queue_centered(
+            ctx,
+            hint,
+            center - Vec2::new(0.0, text_size * 2.0),
+            text_size,
+            Color::WHITE,
+        );
+        let mut lines = Vec::new();
+        if let Some(goal) = self.goal {
+            lines.push(format!("goal met: {}", goal.describe()));
+        }
+        lines.extend([
+            format!("score {}", self.entry.score),
+            format!(
+                "time {}",
+                scores::format_duration(self.entry.duration_secs),
+            ),
+            format!("length {}", self.entry.length),
+            format!("seed {}", self.entry.seed),
+        ]);
+        if let Some(wins) = self.wins {
+            lines.push(format!("boards cleared {}", wins));
+        }
+        for (i, line) in lines.iter().enumerate() {
+            render::queue_centered(
+                ctx,
+                line,
+                center + Vec2::new(0.0, i as f32 * text_size * 1.2),
+                text_size,
+                Color::from_rgb_u32(0xbfbfbf),
+            );
+        }
+        Ok(())
+    }
+
+    fn key_down(
+        &mut self,
+        ctx: &mut Context,
+        shared: &mut Shared,
+        keycode: KeyCode,
+    ) -> Transition {
+        match keycode {
+            KeyCode::Space => {
+                let playing = match self.next_level(shared) {
+                    Some(next) => Playing::campaign(ctx, shared, next),
+                    None => Playing::restart(ctx, shared, &self.kind),
+                };
+                Transition::Reset(Box::new(playing))
+            }
+            KeyCode::Escape => Transition::Home,
+            _ => Transition::None,
+        }
+    }
+
+    fn is_overlay(&self) -> bool {
+        true
+    }
+}
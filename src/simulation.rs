@@ -0,0 +1,981 @@
+use crate::config::{Config, Fruit, GoalKind, Spawn};
+use crate::grid::{self, Grid};
+
+use glam::IVec2;
+
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+use serde::{Deserialize, Serialize};
+
+use std::collections::{HashSet, VecDeque};
+use std::vec::Vec;
+
+/// How many turns can wait for the snake's next moves. Further turns are
+/// dropped until the snake catches up.
+pub(crate) const MAX_QUEUED_TURNS: usize = 3;
+
+/// How many random free squares `FreeCells::choose_where` tries before it
+/// looks through them all.
+const CHOOSE_ATTEMPTS: u32 = 32;
+
+/// The game rules, independent of any window or input device. Each call to
+/// `step` advances the game by one fixed-rate frame.
+pub struct Simulation {
+    pub config: Config,
+    pub seed: u64,
+    pub score: u32,
+    pub live: bool,
+    /// Set when the run ended in a win: the board filled up or the goal
+    /// was met.
+    pub won: bool,
+    /// What killed the snake, once it is dead.
+    pub death: Option<DeathCause>,
+    pub shielded: bool,
+    pub snake: Snake,
+    pub apple: IVec2,
+    pub blueberry: Option<IVec2>,
+    pub golden_apple: Option<IVec2>,
+    pub orange: Option<IVec2>,
+    pub stone_walls: HashSet<IVec2>,
+    pub grow_buffer: u32,
+    pub queued_turns: VecDeque<Direction>,
+    pub direction: Option<Direction>,
+    pub frame_data: FrameData,
+    pub tick: u64,
+    pub turns: Vec<Turn>,
+    pub apples_eaten: u32,
+    pub walls_broken: u32,
+    pub free_cells: FreeCells,
+    rng: ChaCha8Rng,
+}
+
+/// A committed change of direction, as recorded for replays.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Turn {
+    pub tick: u64,
+    pub direction: Direction,
+}
+
+/// What happened during a single call to `Simulation::step`.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum StepOutcome {
+    /// No direction has been chosen yet, so the game has not started.
+    Idle,
+    /// A frame passed but the snake was not due to move.
+    Waiting,
+    /// The snake moved one square.
+    Moved,
+    /// The snake is dead.
+    Died,
+    /// The board is full, so there is nowhere left for an apple.
+    Won,
+}
+
+/// Why a run ended in death.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+pub enum DeathCause {
+    /// Ran off the edge of the board.
+    Edge,
+    /// Ran into its own body.
+    SelfCollision,
+    /// Ran into a stone wall without a shield.
+    StoneWall,
+    /// Ran into the other snake's body, in versus.
+    Rival,
+    /// Met the other snake head to head, in versus.
+    HeadOn,
+}
+
+impl DeathCause {
+    pub fn name(self) -> &'static str {
+        match self {
+            DeathCause::Edge => "edge",
+            DeathCause::SelfCollision => "self",
+            DeathCause::StoneWall => "wall",
+            DeathCause::Rival => "rival",
+            DeathCause::HeadOn => "head-on",
+        }
+    }
+}
+
+/// The snake's squares from tail to head, with a grid counting the
+/// segments on each square so collisions are checked without a search.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Snake {
+    body: VecDeque<IVec2>,
+    segments: Grid<u8>,
+    off_limits_range: i32,
+    /// Whether the off-limits area carries on across the edges.
+    wrap: bool,
+}
+
+/// The squares with nothing on them or too close to the snake, kept in a
+/// list so a random one can be picked in constant time. Each square counts
+/// what blocks it and is free while the count is zero.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct FreeCells {
+    blockers: Grid<u16>,
+    /// Where each free square sits in `cells`.
+    index: Grid<Option<usize>>,
+    cells: Vec<IVec2>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct FrameData {
+    frame: u8,
+    pub frame_delay: f32,
+    pub(crate) bb_waiter: Waiter,
+    pub(crate) ga_waiter: Waiter,
+    pub(crate) or_waiter: Waiter,
+    pub(crate) sw_waiter: Waiter,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub(crate) struct Waiter {
+    frame: u32,
+    update_freq: u32,
+}
+
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub enum Direction {
+    UP, DOWN, LEFT, RIGHT,
+}
+
+impl Direction {
+    pub const ALL: [Direction; 4] = [
+        Direction::UP,
+        Direction::DOWN,
+        Direction::LEFT,
+        Direction::RIGHT,
+    ];
+
+    pub fn delta(self) -> IVec2 {
+        match self {
+            Direction::UP => glam::const_ivec2!([0, -1]),
+            Direction::DOWN => glam::const_ivec2!([0, 1]),
+            Direction::LEFT => glam::const_ivec2!([-1, 0]),
+            Direction::RIGHT => glam::const_ivec2!([1, 0]),
+        }
+    }
+
+    /// The name used in replays and the bot protocol.
+    pub fn name(self) -> &'static str {
+        match self {
+            Direction::UP => "UP",
+            Direction::DOWN => "DOWN",
+            Direction::LEFT => "LEFT",
+            Direction::RIGHT => "RIGHT",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Direction> {
+        match name {
+            "UP" => Some(Direction::UP),
+            "DOWN" => Some(Direction::DOWN),
+            "LEFT" => Some(Direction::LEFT),
+            "RIGHT" => Some(Direction::RIGHT),
+            _ => None,
+        }
+    }
+
+    pub fn opposite(self) -> Direction {
+        match self {
+            Direction::UP => Direction::DOWN,
+            Direction::DOWN => Direction::UP,
+            Direction::LEFT => Direction::RIGHT,
+            Direction::RIGHT => Direction::LEFT,
+        }
+    }
+
+    pub(crate) fn is_vertical(self) -> bool {
+        matches!(self, Direction::UP | Direction::DOWN)
+    }
+}
+
+impl FreeCells {
+    pub fn new(dimensions: IVec2) -> Self {
+        let cells: Vec<IVec2> = (0..dimensions.y)
+            .flat_map(|y| (0..dimensions.x).map(move |x| IVec2::new(x, y)))
+            .collect();
+        let mut index = Grid::new(dimensions, None);
+        for (i, pos) in cells.iter().enumerate() {
+            *index.get_mut(*pos).unwrap() = Some(i);
+        }
+        Self {
+            blockers: Grid::new(dimensions, 0),
+            index,
+            cells,
+        }
+    }
+
+    /// Adds one blocker to `pos`. Squares off the board are ignored.
+    pub fn block(&mut self, pos: IVec2) {
+        let blockers = match self.blockers.get_mut(pos) {
+            Some(blockers) => blockers,
+            None => return,
+        };
+        *blockers += 1;
+        if let Some(i) = self.index.get_mut(pos).unwrap().take() {
+            self.cells.swap_remove(i);
+            if let Some(moved) = self.cells.get(i) {
+                *self.index.get_mut(*moved).unwrap() = Some(i);
+            }
+        }
+    }
+
+    /// Removes one blocker from `pos`, freeing it once none are left.
+    pub fn unblock(&mut self, pos: IVec2) {
+        let blockers = match self.blockers.get_mut(pos) {
+            Some(blockers) => blockers,
+            None => return,
+        };
+        *blockers -= 1;
+        if *blockers == 0 {
+            *self.index.get_mut(pos).unwrap() = Some(self.cells.len());
+            self.cells.push(pos);
+        }
+    }
+
+    pub(crate) fn choose(&self, rng: &mut ChaCha8Rng) -> Option<IVec2> {
+        if self.cells.is_empty() {
+            return None;
+        }
+        Some(self.cells[rng.gen_range(0..self.cells.len())])
+    }
+
+    /// Picks a random free square that `keep` accepts. Random picks are
+    /// tried first, which is quick while most squares are accepted, and
+    /// only when those miss are all the free squares searched.
+    pub(crate) fn choose_where(
+        &self,
+        rng: &mut ChaCha8Rng,
+        keep: impl Fn(IVec2) -> bool,
+    ) -> Option<IVec2> {
+        for _ in 0..CHOOSE_ATTEMPTS {
+            let pos = self.choose(rng)?;
+            if keep(pos) {
+                return Some(pos);
+            }
+        }
+        let cells: Vec<IVec2> = self.cells.iter()
+            .copied()
+            .filter(|pos| keep(*pos))
+            .collect();
+        if cells.is_empty() {
+            return None;
+        }
+        Some(cells[rng.gen_range(0..cells.len())])
+    }
+}
+
+impl Snake {
+    pub fn new(
+        start_pos: IVec2,
+        dimensions: IVec2,
+        off_limits_range: i32,
+        wrap: bool,
+        free_cells: &mut FreeCells,
+    ) -> Self {
+        let mut snake = Self {
+            body: VecDeque::new(),
+            segments: Grid::new(dimensions, 0),
+            off_limits_range,
+            wrap,
+        };
+        snake.grow(start_pos, free_cells);
+        snake
+    }
+
+    pub fn head(&self) -> IVec2 {
+        *self.body.back().unwrap()
+    }
+
+    pub fn tail(&self) -> IVec2 {
+        *self.body.front().unwrap()
+    }
+
+    /// The snake always has a head, so it is never empty.
+    #[allow(clippy::len_without_is_empty)]
+    pub fn len(&self) -> usize {
+        self.body.len()
+    }
+
+    /// The squares from tail to head.
+    pub fn iter(&self) -> std::collections::vec_deque::Iter<'_, IVec2> {
+        self.body.iter()
+    }
+
+    pub fn contains(&self, pos: IVec2) -> bool {
+        self.segments.get(pos).is_some_and(|&count| count > 0)
+    }
+
+    /// The squares on the board within `off_limits_range` of `pos`, where
+    /// nothing new may be placed while the snake is on `pos`. With wrapping
+    /// edges the area carries on across them; otherwise it is cut off.
+    /// Config validation keeps the area smaller than the board, so no
+    /// square comes up twice.
+    fn off_limits(&self, pos: IVec2) -> impl Iterator<Item = IVec2> {
+        let range = self.off_limits_range;
+        let dimensions = self.segments.dimensions();
+        let wrap = self.wrap;
+        let (low, high) = if wrap {
+            (pos - range, pos + range)
+        } else {
+            (
+                (pos - range).max(IVec2::ZERO),
+                (pos + range).min(dimensions - IVec2::ONE),
+            )
+        };
+        (low.x..=high.x).flat_map(move |x| {
+            (low.y..=high.y).map(move |y| match wrap {
+                true => grid::wrap(IVec2::new(x, y), dimensions),
+                false => IVec2::new(x, y),
+            })
+        })
+    }
+
+    /// Moves the head onto `pos`. Fails if `pos` is part of the body, other
+    /// than the tail which is about to move out of the way.
+    pub fn grow(&mut self, pos: IVec2, free_cells: &mut FreeCells) -> bool {
+        if self.contains(pos) && self.tail() != pos {
+            return false;
+        }
+        self.body.push_back(pos);
+        *self.segments.get_mut(pos).expect("snake is on the board") += 1;
+        for square in self.off_limits(pos) {
+            free_cells.block(square);
+        }
+        true
+    }
+
+    pub fn shrink(&mut self, free_cells: &mut FreeCells) {
+        let elem = self.body.pop_front().unwrap();
+        *self.segments.get_mut(elem).unwrap() -= 1;
+        for square in self.off_limits(elem) {
+            free_cells.unblock(square);
+        }
+    }
+}
+
+impl FrameData {
+    pub(crate) fn new(config: &Config) -> Self {
+        Self {
+            frame: 0,
+            frame_delay: config.starting_frame_delay as f32,
+            bb_waiter: Waiter::new(config.bb_gen_frames),
+            ga_waiter: Waiter::new(config.ga_gen_frames),
+            or_waiter: Waiter::new(config.or_gen_frames),
+            sw_waiter: Waiter::new(config.sw_gen_frames),
+        }
+    }
+
+    pub(crate) fn next_frame(&mut self) {
+        self.frame += 1;
+    }
+
+    pub(crate) fn time_to_update(&mut self) -> bool {
+        if self.frame >= self.frame_delay.ceil() as u8 {
+            self.frame = 0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+impl Waiter {
+    fn new(update_freq: u32) -> Self {
+        Self {
+            frame: 0,
+            update_freq,
+        }
+    }
+
+    pub(crate) fn next_frame(&mut self) {
+        self.frame += 1;
+    }
+
+    pub(crate) fn time_to_update(&mut self) -> bool {
+        if self.frame >= self.update_freq {
+            self.frame = 0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+fn invalid_coord() -> IVec2 {
+    glam::const_ivec2!([-1, -1])
+}
+
+impl Simulation {
+    /// Starts a new game. Every random placement is drawn from a single
+    /// stream seeded by `seed`, so the same seed and inputs always produce
+    /// the same game.
+    pub fn new(seed: u64, config: Config) -> Simulation {
+        let dimensions = config.dimensions;
+        let mut free_cells = FreeCells::new(dimensions);
+        let snake = Snake::new(
+            config.start_pos(),
+            dimensions,
+            config.off_limits_range,
+            config.wrap,
+            &mut free_cells,
+        );
+        let mut sim = Simulation {
+            seed,
+            score: 0,
+            live: true,
+            won: false,
+            death: None,
+            shielded: false,
+            snake,
+            apple: invalid_coord(),
+            blueberry: None,
+            golden_apple: None,
+            orange: None,
+            stone_walls: HashSet::new(),
+            grow_buffer: 0,
+            queued_turns: VecDeque::with_capacity(MAX_QUEUED_TURNS),
+            direction: config.start_direction,
+            frame_data: FrameData::new(&config),
+            tick: 0,
+            turns: Vec::new(),
+            apples_eaten: 0,
+            walls_broken: 0,
+            free_cells,
+            rng: ChaCha8Rng::seed_from_u64(seed),
+            config,
+        };
+        for wall in &sim.config.walls {
+            if sim.stone_walls.insert(*wall) {
+                sim.free_cells.block(*wall);
+            }
+        }
+        for (fruit, pos) in &sim.config.fruits {
+            sim.free_cells.block(*pos);
+            match fruit {
+                Fruit::Apple => sim.apple = *pos,
+                Fruit::Blueberry => sim.blueberry = Some(*pos),
+                Fruit::GoldenApple => sim.golden_apple = Some(*pos),
+                Fruit::Orange => sim.orange = Some(*pos),
+            }
+        }
+        // Config validation makes sure the walls, the fixed fruit and the
+        // snake's off-limits area leave some room, so a new board always
+        // has an apple.
+        if !sim.config.fruits.contains_key(&Fruit::Apple) {
+            sim.apple = sim.gen_open_square()
+                .expect("a new board has room for an apple");
+        }
+        sim
+    }
+
+    /// Picks a random free square and marks it as taken. Returns `None` if
+    /// the board is full.
+    fn gen_open_square(&mut self) -> Option<IVec2> {
+        let sq = self.free_cells.choose(&mut self.rng)?;
+        self.free_cells.block(sq);
+        Some(sq)
+    }
+
+    fn die(&mut self, cause: DeathCause) -> StepOutcome {
+        self.live = false;
+        self.death = Some(cause);
+        StepOutcome::Died
+    }
+
+    fn win(&mut self) -> StepOutcome {
+        self.won = true;
+        self.live = false;
+        StepOutcome::Won
+    }
+
+    /// How far the run has got towards the goal, if there is one.
+    pub fn goal_progress(&self) -> Option<u32> {
+        let progress = match self.config.goal?.kind {
+            GoalKind::Apples => self.apples_eaten,
+            GoalKind::Length => self.snake.len() as u32 + self.grow_buffer,
+            GoalKind::WallsBroken => self.walls_broken,
+            GoalKind::Score => self.score,
+        };
+        Some(progress)
+    }
+
+    /// Whether the goal has been met. Checked wherever points are awarded.
+    fn goal_met(&self) -> bool {
+        match (self.config.goal, self.goal_progress()) {
+            (Some(goal), Some(progress)) => progress >= goal.target,
+            _ => false,
+        }
+    }
+
+    fn end_outcome(&self) -> StepOutcome {
+        if self.won {
+            StepOutcome::Won
+        } else {
+            StepOutcome::Died
+        }
+    }
+
+    /// The square one move from `pos` in `direction`. With wrapping edges
+    /// it is always on the board.
+    pub fn next_square(&self, pos: IVec2, direction: Direction) -> IVec2 {
+        let next = pos + direction.delta();
+        if self.config.wrap {
+            grid::wrap(next, self.config.dimensions)
+        } else {
+            next
+        }
+    }
+
+    /// Whether the game has started, i.e. the snake has been given a
+    /// direction.
+    pub fn started(&self) -> bool {
+        self.direction.is_some() || !self.queued_turns.is_empty()
+    }
+
+    /// Whether the snake may turn to `direction` after any turns already
+    /// queued. Turns along the axis of travel are rejected so the snake can
+    /// never reverse into itself.
+    pub fn can_turn(&self, direction: Direction) -> bool {
+        if self.queued_turns.len() >= MAX_QUEUED_TURNS {
+            return false;
+        }
+        match self.queued_turns.back().copied().or(self.direction) {
+            None => true,
+            Some(current) => current.is_vertical() != direction.is_vertical(),
+        }
+    }
+
+    /// Queues a turn. Queued turns are applied one per move, in order.
+    pub fn turn(&mut self, direction: Direction) {
+        if self.can_turn(direction) {
+            self.queued_turns.push_back(direction);
+        }
+    }
+
+    /// Applies `input` as a turn request, then advances the game by one
+    /// frame.
+    pub fn step(&mut self, input: Option<Direction>) -> StepOutcome {
+        if !self.live {
+            return self.end_outcome();
+        }
+        if let Some(direction) = input {
+            self.turn(direction);
+        }
+        if !self.started() {
+            return StepOutcome::Idle;
+        }
+
+        let tick = self.tick;
+        self.tick += 1;
+        self.frame_data.next_frame();
+        if !self.frame_data.time_to_update() {
+            return StepOutcome::Waiting;
+        }
+
+        // Update direction
+        if let Some(direction) = self.queued_turns.pop_front() {
+            self.direction = Some(direction);
+            self.turns.push(Turn { tick, direction });
+        }
+        let direction = match self.direction {
+            Some(direction) => direction,
+            _ => panic!("unexpected snake direction"),
+        };
+
+        // Move snake
+        let new_head = self.next_square(self.snake.head(), direction);
+        let dimensions = self.config.dimensions;
+        if new_head.x < 0 || new_head.x >= dimensions.x ||
+            new_head.y < 0 || new_head.y >= dimensions.y {
+            return self.die(DeathCause::Edge);
+        }
+
+        // Check for stone wall collision
+        if self.stone_walls.contains(&new_head) {
+            if self.shielded {
+                self.shielded = false;
+                self.stone_walls.remove(&new_head);
+                self.free_cells.unblock(new_head);
+                self.score += self.config.wall_break_worth;
+                self.walls_broken += 1;
+                if self.goal_met() {
+                    return self.win();
+                }
+            } else {
+                return self.die(DeathCause::StoneWall);
+            }
+        }
+
+        // Move the head, unless it runs into the body
+        if !self.snake.grow(new_head, &mut self.free_cells) {
+            return self.die(DeathCause::SelfCollision);
+        }
+
+        // Apple collection
+        if new_head == self.apple {
+            match self.gen_open_square() {
+                Some(apple) => {
+                    self.free_cells.unblock(self.apple);
+                    self.apple = apple;
+                }
+                None => {
+                    self.score += 1;
+                    self.apples_eaten += 1;
+                    return self.win();
+                }
+            }
+            self.score += 1;
+            self.apples_eaten += 1;
+            self.frame_data.frame_delay *= self.config.frame_delay_decay;
+            self.grow_buffer += 1;
+            if self.goal_met() {
+                return self.win();
+            }
+        }
+
+        // Blueberry collection
+        if let Some(blueberry) = self.blueberry {
+            if new_head == blueberry {
+                self.blueberry = None;
+                self.free_cells.unblock(blueberry);
+                self.score += 1;
+                self.frame_data.frame_delay += self.config.frame_delay_inc;
+                if self.goal_met() {
+                    return self.win();
+                }
+            }
+        } else if self.config.spawns.contains(&Spawn::Blueberry) {
+            self.frame_data.bb_waiter.next_frame();
+            if self.frame_data.bb_waiter.time_to_update() {
+                self.blueberry = self.gen_open_square();
+            }
+        }
+
+        // Golden apple collection
+        if let Some(golden_apple) = self.golden_apple {
+            if new_head == golden_apple {
+                self.golden_apple = None;
+                self.free_cells.unblock(golden_apple);
+                self.score += self.config.golden_apple_worth;
+                self.frame_data.frame_delay *= self.config.frame_delay_decay;
+                self.grow_buffer += self.config.golden_apple_worth;
+                if self.goal_met() {
+                    return self.win();
+                }
+            }
+        } else if self.config.spawns.contains(&Spawn::GoldenApple) {
+            self.frame_data.ga_waiter.next_frame();
+            if self.frame_data.ga_waiter.time_to_update() {
+                self.golden_apple = self.gen_open_square();
+            }
+        }
+
+        // Orange collection
+        if let Some(orange) = self.orange {
+            if new_head == orange {
+                self.orange = None;
+                self.free_cells.unblock(orange);
+                self.shielded = true;
+                self.score += 1;
+                if self.goal_met() {
+                    return self.win();
+                }
+            }
+        } else if !self.shielded &&
+            self.config.spawns.contains(&Spawn::Orange)
+        {
+            self.frame_data.or_waiter.next_frame();
+            if self.frame_data.or_waiter.time_to_update() {
+                self.orange = self.gen_open_square();
+            }
+        }
+
+        // Stone wall generator
+        if self.config.spawns.contains(&Spawn::StoneWall) {
+            self.frame_data.sw_waiter.next_frame();
+            if self.frame_data.sw_waiter.time_to_update() {
+                if let Some(new_wall) = self.gen_open_square() {
+                    self.stone_walls.insert(new_wall);
+                }
+            }
+        }
+
+        if self.grow_buffer == 0 {
+            self.snake.shrink(&mut self.free_cells);
+        } else {
+            self.grow_buffer -= 1;
+        }
+        StepOutcome::Moved
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::collections::BTreeMap;
+
+    /// A small board where the snake moves on every step and nothing
+    /// spawns by itself. The apple sits out of the way in a corner.
+    fn config() -> Config {
+        Config {
+            dimensions: IVec2::new(10, 10),
+            starting_frame_delay: 1,
+            off_limits_range: 1,
+            spawns: Vec::new(),
+            start: Some(IVec2::new(2, 2)),
+            fruits: BTreeMap::from([(Fruit::Apple, IVec2::new(9, 9))]),
+            ..Config::default()
+        }
+    }
+
+    #[test]
+    fn running_off_the_edge_ends_the_game() {
+        let mut sim = Simulation::new(1, config());
+        assert_eq!(sim.step(Some(Direction::UP)), StepOutcome::Moved);
+        assert_eq!(sim.step(None), StepOutcome::Moved);
+        assert_eq!(sim.snake.head(), IVec2::new(2, 0));
+        assert_eq!(sim.step(None), StepOutcome::Died);
+        assert_eq!(sim.death, Some(DeathCause::Edge));
+        assert!(!sim.live);
+    }
+
+    #[test]
+    fn running_into_itself_ends_the_game() {
+        let mut sim = Simulation::new(1, config());
+        sim.grow_buffer = 4;
+        sim.step(Some(Direction::RIGHT));
+        for _ in 0..3 {
+            assert_eq!(sim.step(None), StepOutcome::Moved);
+        }
+        assert_eq!(sim.snake.len(), 5);
+        assert_eq!(sim.step(Some(Direction::DOWN)), StepOutcome::Moved);
+        assert_eq!(sim.step(Some(Direction::LEFT)), StepOutcome::Moved);
+        assert_eq!(sim.step(Some(Direction::UP)), StepOutcome::Died);
+        assert_eq!(sim.death, Some(DeathCause::SelfCollision));
+    }
+
+    #[test]
+    fn a_wall_kills_without_a_shield() {
+        let config = Config {
+            walls: vec![IVec2::new(4, 2)],
+            ..config()
+        };
+        let mut sim = Simulation::new(1, config);
+        assert_eq!(sim.step(Some(Direction::RIGHT)), StepOutcome::Moved);
+        assert_eq!(sim.step(None), StepOutcome::Died);
+        assert_eq!(sim.death, Some(DeathCause::StoneWall));
+    }
+
+    #[test]
+    fn a_shield_breaks_a_wall() {
+        let config = Config {
+            walls: vec![IVec2::new(4, 2)],
+            ..config()
+        };
+        let mut sim = Simulation::new(1, config);
+        sim.shielded = true;
+        sim.step(Some(Direction::RIGHT));
+        assert_eq!(sim.step(None), StepOutcome::Moved);
+        assert_eq!(sim.snake.head(), IVec2::new(4, 2));
+        assert!(!sim.shielded);
+        assert!(sim.stone_walls.is_empty());
+        assert_eq!(sim.score, sim.config.wall_break_worth);
+        assert_eq!(sim.walls_broken, 1);
+    }
+
+    #[test]
+    fn an_apple_grows_scores_and_speeds_up() {
+        let config = Config {
+            fruits: BTreeMap::from([(Fruit::Apple, IVec2::new(4, 2))]),
+            ..config()
+        };
+        let mut sim = Simulation::new(1, config);
+        let frame_delay = sim.frame_data.frame_delay;
+        sim.step(Some(Direction::RIGHT));
+        assert_eq!(sim.step(None), StepOutcome::Moved);
+        assert_eq!(sim.score, 1);
+        assert_eq!(sim.apples_eaten, 1);
+        assert_eq!(sim.snake.len(), 2);
+        assert_ne!(sim.apple, IVec2::new(4, 2));
+        assert_eq!(
+            sim.frame_data.frame_delay,
+            frame_delay * sim.config.frame_delay_decay,
+        );
+    }
+
+    #[test]
+    fn reversing_is_ignored() {
+        let mut sim = Simulation::new(1, config());
+        sim.step(Some(Direction::RIGHT));
+        assert!(!sim.can_turn(Direction::LEFT));
+        assert_eq!(sim.step(Some(Direction::LEFT)), StepOutcome::Moved);
+        assert_eq!(sim.direction, Some(Direction::RIGHT));
+        assert_eq!(sim.snake.head(), IVec2::new(4, 2));
+    }
+
+    #[test]
+    fn turns_past_the_queue_limit_are_dropped() {
+        let mut sim = Simulation::new(1, config());
+        for direction in [
+            Direction::UP,
+            Direction::LEFT,
+            Direction::DOWN,
+            Direction::RIGHT,
+        ] {
+            sim.turn(direction);
+        }
+        assert_eq!(sim.queued_turns.len(), MAX_QUEUED_TURNS);
+        assert_eq!(
+            sim.queued_turns,
+            [Direction::UP, Direction::LEFT, Direction::DOWN],
+        );
+    }
+
+    fn wrapping(start: IVec2) -> Config {
+        Config {
+            wrap: true,
+            start: Some(start),
+            ..config()
+        }
+    }
+
+    #[test]
+    fn wrapping_edges_lead_to_the_opposite_side() {
+        let crossings = [
+            (IVec2::new(0, 2), Direction::LEFT, IVec2::new(9, 2)),
+            (IVec2::new(9, 2), Direction::RIGHT, IVec2::new(0, 2)),
+            (IVec2::new(2, 0), Direction::UP, IVec2::new(2, 9)),
+            (IVec2::new(2, 9), Direction::DOWN, IVec2::new(2, 0)),
+        ];
+        for (start, direction, end) in crossings {
+            let mut sim = Simulation::new(1, wrapping(start));
+            assert_eq!(sim.step(Some(direction)), StepOutcome::Moved);
+            assert_eq!(sim.snake.head(), end);
+            assert!(sim.live);
+        }
+    }
+
+    #[test]
+    fn off_limits_carries_on_across_the_seam() {
+        let corner = IVec2::ZERO;
+        let across = [
+            IVec2::new(9, 9),
+            IVec2::new(9, 0),
+            IVec2::new(9, 1),
+            IVec2::new(0, 9),
+            IVec2::new(1, 9),
+        ];
+        // The apple is kept clear of the corner's neighbours.
+        let apple = BTreeMap::from([(Fruit::Apple, IVec2::new(5, 5))]);
+        let config = Config { fruits: apple, ..wrapping(corner) };
+        let sim = Simulation::new(1, config.clone());
+        for pos in across {
+            assert_ne!(sim.free_cells.blockers.get(pos), Some(&0), "{}", pos);
+        }
+        let sim = Simulation::new(1, Config { wrap: false, ..config });
+        for pos in across {
+            assert_eq!(sim.free_cells.blockers.get(pos), Some(&0), "{}", pos);
+        }
+
+        // So a new apple never lands next to the head, on either side.
+        let config = Config { fruits: BTreeMap::new(), ..wrapping(corner) };
+        for seed in 0..200 {
+            let sim = Simulation::new(seed, config.clone());
+            let gap = (sim.apple - corner).abs();
+            let gap = gap.min(config.dimensions - gap);
+            assert!(gap.max_element() > 1, "seed {}", seed);
+        }
+    }
+
+    #[test]
+    fn running_into_itself_across_the_seam() {
+        let mut sim = Simulation::new(1, wrapping(IVec2::new(1, 2)));
+        sim.grow_buffer = 4;
+        assert_eq!(sim.step(Some(Direction::LEFT)), StepOutcome::Moved);
+        assert_eq!(sim.step(None), StepOutcome::Moved);
+        assert_eq!(sim.step(Some(Direction::DOWN)), StepOutcome::Moved);
+        assert_eq!(sim.step(Some(Direction::RIGHT)), StepOutcome::Moved);
+        assert_eq!(sim.snake.head(), IVec2::new(0, 3));
+        assert_eq!(sim.step(Some(Direction::UP)), StepOutcome::Died);
+        assert_eq!(sim.death, Some(DeathCause::SelfCollision));
+    }
+
+    /// Checks `free_cells` against a plain count of blockers per square.
+    fn assert_matches(free_cells: &FreeCells, blockers: &Grid<u16>) {
+        let dimensions = blockers.dimensions();
+        let mut free = 0;
+        for pos in (0..dimensions.y)
+            .flat_map(|y| (0..dimensions.x).map(move |x| IVec2::new(x, y)))
+        {
+            match free_cells.index.get(pos).unwrap() {
+                Some(index) => {
+                    assert_eq!(blockers.get(pos), Some(&0));
+                    assert_eq!(free_cells.cells[*index], pos);
+                    free += 1;
+                }
+                None => assert_ne!(blockers.get(pos), Some(&0)),
+            }
+        }
+        assert_eq!(free_cells.cells.len(), free);
+    }
+
+    #[test]
+    fn free_cells_track_blocking_in_any_order() {
+        let dimensions = IVec2::new(6, 5);
+        let mut free_cells = FreeCells::new(dimensions);
+        let mut blockers = Grid::new(dimensions, 0u16);
+        let mut rng = ChaCha8Rng::seed_from_u64(9);
+        for _ in 0..2_000 {
+            let pos = IVec2::new(
+                rng.gen_range(0..dimensions.x),
+                rng.gen_range(0..dimensions.y),
+            );
+            let count = blockers.get_mut(pos).unwrap();
+            // Squares pile up more than one blocker, as off-limits areas
+            // overlap.
+            if *count > 0 && rng.gen_bool(0.5) {
+                *count -= 1;
+                free_cells.unblock(pos);
+            } else {
+                *count += 1;
+                free_cells.block(pos);
+            }
+            assert_matches(&free_cells, &blockers);
+            for _ in 0..4 {
+                match free_cells.choose(&mut rng) {
+                    Some(pos) => assert_eq!(blockers.get(pos), Some(&0)),
+                    None => assert!(free_cells.cells.is_empty()),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn choose_where_only_picks_accepted_squares() {
+        let dimensions = IVec2::new(20, 20);
+        let mut free_cells = FreeCells::new(dimensions);
+        free_cells.block(IVec2::new(3, 4));
+        let mut rng = ChaCha8Rng::seed_from_u64(4);
+        for _ in 0..100 {
+            let pos = free_cells.choose_where(&mut rng, |pos| pos.x < 10)
+                .unwrap();
+            assert!(pos.x < 10);
+        }
+        // Too rare for random picks to find, so found by the search.
+        let only = IVec2::new(17, 2);
+        for _ in 0..10 {
+            let pos = free_cells.choose_where(&mut rng, |pos| pos == only);
+            assert_eq!(pos, Some(only));
+        }
+        let blocked = IVec2::new(3, 4);
+        let pos = free_cells.choose_where(&mut rng, |pos| pos == blocked);
+        assert_eq!(pos, None);
+    }
+}
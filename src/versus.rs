@@ -0,0 +1,570 @@
+//! Two players on one board, each with their own snake, score, shield and
+//! growth, racing for the same fruit.
+//!
+//! Both snakes move on the same frames, and each move is judged against
+//! the board as it was before either snake moved. A snake dies running off
+//! the edge, into a stone wall without a shield, into its own body or into
+//! the other snake's body. The other snake's tail does not count when it
+//! is about to move out of the way, which it does not when that snake is
+//! still growing or eats on the same move. Two heads meeting on one
+//! square, or passing through each other, is a head-on collision and
+//! kills both.
+//!
+//! The round ends on the first death, and the snake left alive wins. When
+//! both die on the same move, or the board fills up, the higher score wins
+//! and equal scores are a draw.
+//!
+//! New fruit goes about as far from one head as from the other, where the
+//! board leaves room for it, so neither player is handed it.
+
+use crate::config::{Config, Fruit, Spawn};
+use crate::grid;
+use crate::simulation::{
+    DeathCause, Direction, FrameData, FreeCells, Snake, StepOutcome,
+    MAX_QUEUED_TURNS,
+};
+
+use glam::IVec2;
+
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+
+use serde::{Deserialize, Serialize};
+
+use std::collections::{HashSet, VecDeque};
+
+pub const PLAYERS: usize = 2;
+
+/// How many more squares a new fruit may be from one head than from the
+/// other.
+const FAIR_MARGIN: i32 = 2;
+
+/// One player's snake and everything that belongs to it alone.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Player {
+    pub snake: Snake,
+    pub score: u32,
+    pub shielded: bool,
+    pub grow_buffer: u32,
+    pub queued_turns: VecDeque<Direction>,
+    pub direction: Option<Direction>,
+    /// What killed the snake, once it is dead.
+    pub death: Option<DeathCause>,
+}
+
+/// How a round ended.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum VersusResult {
+    /// The player at this index won.
+    Winner(usize),
+    Draw,
+}
+
+/// The versus rules. Like `Simulation`, each call to `step` advances the
+/// round by one frame, and the same seed and inputs always produce the
+/// same round. The whole round can be cloned or serialized, to be picked
+/// up again from the same point.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Versus {
+    pub config: Config,
+    pub seed: u64,
+    pub players: [Player; PLAYERS],
+    /// Set once the round is over.
+    pub result: Option<VersusResult>,
+    pub apple: IVec2,
+    pub blueberry: Option<IVec2>,
+    pub golden_apple: Option<IVec2>,
+    pub orange: Option<IVec2>,
+    pub stone_walls: HashSet<IVec2>,
+    pub frame_data: FrameData,
+    pub tick: u64,
+    pub free_cells: FreeCells,
+    rng: ChaCha8Rng,
+}
+
+/// Where the snakes start: level with each other, a quarter of the board
+/// in from the left and right edges.
+pub fn start_positions(dimensions: IVec2) -> [IVec2; PLAYERS] {
+    let y = dimensions.y / 2;
+    [
+        IVec2::new(dimensions.x / 4, y),
+        IVec2::new(dimensions.x - 1 - dimensions.x / 4, y),
+    ]
+}
+
+/// Moves apart on the board, the short way round with wrapping edges.
+fn distance(config: &Config, from: IVec2, to: IVec2) -> i32 {
+    let mut gap = (to - from).abs();
+    if config.wrap {
+        gap = gap.min(config.dimensions - gap);
+    }
+    gap.x + gap.y
+}
+
+impl Player {
+    fn new(start: IVec2, config: &Config, free_cells: &mut FreeCells) -> Self {
+        Self {
+            snake: Snake::new(
+                start,
+                config.dimensions,
+                config.off_limits_range,
+                config.wrap,
+                free_cells,
+            ),
+            score: 0,
+            shielded: false,
+            grow_buffer: 0,
+            queued_turns: VecDeque::with_capacity(MAX_QUEUED_TURNS),
+            direction: None,
+            death: None,
+        }
+    }
+
+    /// Whether the player has chosen a direction to set off in.
+    pub fn started(&self) -> bool {
+        self.direction.is_some() || !self.queued_turns.is_empty()
+    }
+
+    /// Whether the snake may turn to `direction`, by the same rule as in
+    /// `Simulation::can_turn`.
+    pub fn can_turn(&self, direction: Direction) -> bool {
+        if self.queued_turns.len() >= MAX_QUEUED_TURNS {
+            return false;
+        }
+        match self.queued_turns.back().copied().or(self.direction) {
+            None => true,
+            Some(current) => current.is_vertical() != direction.is_vertical(),
+        }
+    }
+
+    pub fn turn(&mut self, direction: Direction) {
+        if self.can_turn(direction) {
+            self.queued_turns.push_back(direction);
+        }
+    }
+}
+
+impl Versus {
+    /// Starts a round on the board `config` describes, which must pass
+    /// `Config::validate_versus`. Its start is not used, and walls or fixed
+    /// fruit on either start square are left out.
+    pub fn new(seed: u64, config: Config) -> Self {
+        let mut free_cells = FreeCells::new(config.dimensions);
+        let starts = start_positions(config.dimensions);
+        let players = starts.map(|start| {
+            Player::new(start, &config, &mut free_cells)
+        });
+        let mut versus = Self {
+            seed,
+            players,
+            result: None,
+            apple: IVec2::new(-1, -1),
+            blueberry: None,
+            golden_apple: None,
+            orange: None,
+            stone_walls: HashSet::new(),
+            frame_data: FrameData::new(&config),
+            tick: 0,
+            free_cells,
+            rng: ChaCha8Rng::seed_from_u64(seed),
+            config,
+        };
+        for wall in &versus.config.walls {
+            if !starts.contains(wall) && versus.stone_walls.insert(*wall) {
+                versus.free_cells.block(*wall);
+            }
+        }
+        for (fruit, pos) in &versus.config.fruits {
+            if starts.contains(pos) {
+                continue;
+            }
+            versus.free_cells.block(*pos);
+            match fruit {
+                Fruit::Apple => versus.apple = *pos,
+                Fruit::Blueberry => versus.blueberry = Some(*pos),
+                Fruit::GoldenApple => versus.golden_apple = Some(*pos),
+                Fruit::Orange => versus.orange = Some(*pos),
+            }
+        }
+        if versus.apple == IVec2::new(-1, -1) {
+            versus.apple = versus.gen_fruit_square()
+                .expect("a new board has room for an apple");
+        }
+        versus
+    }
+
+    /// Picks a free square about as far from both heads and marks it as
+    /// taken, or any free square if none is. Returns `None` if the board is
+    /// full.
+    fn gen_fruit_square(&mut self) -> Option<IVec2> {
+        let heads = self.players.each_ref().map(|player| player.snake.head());
+        let fair = |pos: IVec2| {
+            let gap = distance(&self.config, heads[0], pos) -
+                distance(&self.config, heads[1], pos);
+            gap.abs() <= FAIR_MARGIN
+        };
+        let sq = self.free_cells.choose_where(&mut self.rng, fair)
+            .or_else(|| self.free_cells.choose(&mut self.rng))?;
+        self.free_cells.block(sq);
+        Some(sq)
+    }
+
+    fn next_square(&self, pos: IVec2, direction: Direction) -> IVec2 {
+        let next = pos + direction.delta();
+        if self.config.wrap {
+            grid::wrap(next, self.config.dimensions)
+        } else {
+            next
+        }
+    }
+
+    /// Whether a head moving onto `pos` eats fruit that makes the snake
+    /// grow, which keeps its tail where it is.
+    fn feeds(&self, pos: IVec2) -> bool {
+        pos == self.apple || (self.golden_apple == Some(pos) &&
+            self.config.golden_apple_worth > 0)
+    }
+
+    /// What kills player `index` if the heads move to `new_heads`, if
+    /// anything.
+    fn collision(
+        &self,
+        index: usize,
+        new_heads: [IVec2; PLAYERS],
+    ) -> Option<DeathCause> {
+        let player = &self.players[index];
+        let other = &self.players[1 - index];
+        let new_head = new_heads[index];
+        let dimensions = self.config.dimensions;
+        if new_head.x < 0 || new_head.x >= dimensions.x ||
+            new_head.y < 0 || new_head.y >= dimensions.y {
+            return Some(DeathCause::Edge);
+        }
+        let swapped = new_head == other.snake.head() &&
+            new_heads[1 - index] == player.snake.head();
+        if new_head == new_heads[1 - index] || swapped {
+            return Some(DeathCause::HeadOn);
+        }
+        if self.stone_walls.contains(&new_head) && !player.shielded {
+            return Some(DeathCause::StoneWall);
+        }
+        if player.snake.contains(new_head) && player.snake.tail() != new_head {
+            return Some(DeathCause::SelfCollision);
+        }
+        let tail_moves = other.snake.tail() == new_head &&
+            other.grow_buffer == 0 && !self.feeds(new_heads[1 - index]);
+        if other.snake.contains(new_head) && !tail_moves {
+            return Some(DeathCause::Rival);
+        }
+        None
+    }
+
+    /// A hash of everything that decides how the round goes on, for
+    /// checking that two copies of it are still the same.
+    pub fn checksum(&self) -> u64 {
+        let mut bytes = Vec::new();
+        let add_pos = |bytes: &mut Vec<u8>, pos: IVec2| {
+            bytes.extend(pos.x.to_le_bytes());
+            bytes.extend(pos.y.to_le_bytes());
+        };
+        bytes.extend(self.tick.to_le_bytes());
+        bytes.extend(self.rng.get_word_pos().to_le_bytes());
+        bytes.extend(self.frame_data.frame_delay.to_bits().to_le_bytes());
+        for player in &self.players {
+            bytes.extend(player.score.to_le_bytes());
+            bytes.extend(player.grow_buffer.to_le_bytes());
+            bytes.push(player.shielded as u8);
+            bytes.push(player.direction.map_or(0, |dir| dir as u8 + 1));
+            for pos in player.snake.iter() {
+                add_pos(&mut bytes, *pos);
+            }
+        }
+        let fruit = [
+            Some(self.apple),
+            self.blueberry,
+            self.golden_apple,
+            self.orange,
+        ];
+        for pos in fruit {
+            add_pos(&mut bytes, pos.unwrap_or(IVec2::new(-1, -1)));
+        }
+        // The set's order differs between runs, so sort it first.
+        let mut stone_walls: Vec<IVec2> = self.stone_walls.iter()
+            .copied()
+            .collect();
+        stone_walls.sort_by_key(|pos| (pos.y, pos.x));
+        for pos in stone_walls {
+            add_pos(&mut bytes, pos);
+        }
+
+        // FNV-1a, which unlike the std hasher is the same in every build.
+        bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+            (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+        })
+    }
+
+    /// A simple way to play for player `index`, for headless rounds: the
+    /// move that brings the head closest to the apple without dying on
+    /// the spot. `None` if every move is deadly.
+    pub fn greedy_direction(&self, index: usize) -> Option<Direction> {
+        let player = &self.players[index];
+        let other = &self.players[1 - index];
+        let head = player.snake.head();
+        let dimensions = self.config.dimensions;
+        Direction::ALL.into_iter()
+            .filter(|direction| {
+                player.direction != Some(direction.opposite()) ||
+                    player.snake.len() == 1
+            })
+            .map(|direction| (direction, self.next_square(head, direction)))
+            .filter(|(_, pos)| {
+                let on_board = pos.cmpge(IVec2::ZERO).all() &&
+                    pos.cmplt(dimensions).all();
+                let walled = self.stone_walls.contains(pos) &&
+                    !player.shielded;
+                let bitten = player.snake.contains(*pos) &&
+                    player.snake.tail() != *pos;
+                on_board && !walled && !bitten && !other.snake.contains(*pos)
+            })
+            .min_by_key(|(_, pos)| distance(&self.config, *pos, self.apple))
+            .map(|(direction, _)| direction)
+    }
+
+    /// Ends the round on scores.
+    fn decide_on_scores(&mut self) {
+        let [first, second] = self.players.each_ref().map(|p| p.score);
+        self.result = Some(match first.cmp(&second) {
+            std::cmp::Ordering::Greater => VersusResult::Winner(0),
+            std::cmp::Ordering::Less => VersusResult::Winner(1),
+            std::cmp::Ordering::Equal => VersusResult::Draw,
+        });
+    }
+
+    fn end_outcome(&self) -> StepOutcome {
+        if self.players.iter().any(|player| player.death.is_some()) {
+            StepOutcome::Died
+        } else {
+            StepOutcome::Won
+        }
+    }
+
+    /// Applies each player's input as a turn request, then advances the
+    /// round by one frame. The round waits until both players have chosen
+    /// a direction. It ends with `Died` if a snake died and `Won` if the
+    /// board filled up.
+    pub fn step(
+        &mut self,
+        inputs: [Option<Direction>; PLAYERS],
+    ) -> StepOutcome {
+        if self.result.is_some() {
+            return self.end_outcome();
+        }
+        for (player, input) in self.players.iter_mut().zip(inputs) {
+            if let Some(direction) = input {
+                player.turn(direction);
+            }
+        }
+        if !self.players.iter().all(Player::started) {
+            return StepOutcome::Idle;
+        }
+
+        self.tick += 1;
+        self.frame_data.next_frame();
+        if !self.frame_data.time_to_update() {
+            return StepOutcome::Waiting;
+        }
+
+        for player in &mut self.players {
+            if let Some(direction) = player.queued_turns.pop_front() {
+                player.direction = Some(direction);
+            }
+        }
+        let new_heads = self.players.each_ref().map(|player| {
+            let direction = player.direction.expect("both players started");
+            self.next_square(player.snake.head(), direction)
+        });
+
+        // Judge both moves before either snake moves
+        let deaths = [0, 1].map(|index| self.collision(index, new_heads));
+        if deaths.iter().any(Option::is_some) {
+            for (player, death) in self.players.iter_mut().zip(deaths) {
+                player.death = death;
+            }
+            match deaths {
+                [Some(_), None] => {
+                    self.result = Some(VersusResult::Winner(1));
+                }
+                [None, Some(_)] => {
+                    self.result = Some(VersusResult::Winner(0));
+                }
+                _ => self.decide_on_scores(),
+            }
+            return StepOutcome::Died;
+        }
+
+        // Move both snakes
+        for (index, new_head) in new_heads.into_iter().enumerate() {
+            let player = &mut self.players[index];
+            if self.stone_walls.remove(&new_head) {
+                player.shielded = false;
+                self.free_cells.unblock(new_head);
+                player.score += self.config.wall_break_worth;
+            }
+            player.snake.grow(new_head, &mut self.free_cells);
+        }
+
+        // Fruit collection. The heads are on different squares, so each
+        // fruit goes to one player at most.
+        for (index, new_head) in new_heads.into_iter().enumerate() {
+            if new_head == self.apple {
+                match self.gen_fruit_square() {
+                    Some(apple) => {
+                        self.free_cells.unblock(self.apple);
+                        self.apple = apple;
+                    }
+                    None => {
+                        self.players[index].score += 1;
+                        self.decide_on_scores();
+                        return StepOutcome::Won;
+                    }
+                }
+                let player = &mut self.players[index];
+                player.score += 1;
+                player.grow_buffer += 1;
+                self.frame_data.frame_delay *= self.config.frame_delay_decay;
+            }
+            if self.blueberry == Some(new_head) {
+                self.blueberry = None;
+                self.free_cells.unblock(new_head);
+                self.players[index].score += 1;
+                self.frame_data.frame_delay += self.config.frame_delay_inc;
+            }
+            if self.golden_apple == Some(new_head) {
+                self.golden_apple = None;
+                self.free_cells.unblock(new_head);
+                let player = &mut self.players[index];
+                player.score += self.config.golden_apple_worth;
+                player.grow_buffer += self.config.golden_apple_worth;
+                self.frame_data.frame_delay *= self.config.frame_delay_decay;
+            }
+            if self.orange == Some(new_head) {
+                self.orange = None;
+                self.free_cells.unblock(new_head);
+                let player = &mut self.players[index];
+                player.shielded = true;
+                player.score += 1;
+            }
+        }
+
+        // Spawns, timed as in single player. Oranges wait while both
+        // players hold a shield.
+        let spawns = &self.config.spawns;
+        let all_shielded = self.players.iter().all(|player| player.shielded);
+        let frame_data = &mut self.frame_data;
+        let mut due = Vec::new();
+        if self.blueberry.is_none() && spawns.contains(&Spawn::Blueberry) {
+            frame_data.bb_waiter.next_frame();
+            if frame_data.bb_waiter.time_to_update() {
+                due.push(Spawn::Blueberry);
+            }
+        }
+        if self.golden_apple.is_none() && spawns.contains(&Spawn::GoldenApple)
+        {
+            frame_data.ga_waiter.next_frame();
+            if frame_data.ga_waiter.time_to_update() {
+                due.push(Spawn::GoldenApple);
+            }
+        }
+        if self.orange.is_none() && !all_shielded &&
+            spawns.contains(&Spawn::Orange)
+        {
+            frame_data.or_waiter.next_frame();
+            if frame_data.or_waiter.time_to_update() {
+                due.push(Spawn::Orange);
+            }
+        }
+        if spawns.contains(&Spawn::StoneWall) {
+            frame_data.sw_waiter.next_frame();
+            if frame_data.sw_waiter.time_to_update() {
+                due.push(Spawn::StoneWall);
+            }
+        }
+        for spawn in due {
+            match spawn {
+                Spawn::Blueberry => self.blueberry = self.gen_fruit_square(),
+                Spawn::GoldenApple => {
+                    self.golden_apple = self.gen_fruit_square();
+                }
+                Spawn::Orange => self.orange = self.gen_fruit_square(),
+                Spawn::StoneWall => {
+                    if let Some(wall) = self.free_cells.choose(&mut self.rng) {
+                        self.free_cells.block(wall);
+                        self.stone_walls.insert(wall);
+                    }
+                }
+            }
+        }
+
+        for player in &mut self.players {
+            if player.grow_buffer == 0 {
+                player.snake.shrink(&mut self.free_cells);
+            } else {
+                player.grow_buffer -= 1;
+            }
+        }
+        StepOutcome::Moved
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::collections::BTreeMap;
+
+    /// A 6x6 board where the snakes move on every step and nothing spawns
+    /// by itself. The snakes start at (1, 3) and (4, 3).
+    fn config(apple: IVec2) -> Config {
+        let config = Config {
+            dimensions: IVec2::new(6, 6),
+            starting_frame_delay: 1,
+            off_limits_range: 1,
+            spawns: Vec::new(),
+            fruits: BTreeMap::from([(Fruit::Apple, apple)]),
+            ..Config::default()
+        };
+        assert!(config.validate_versus().is_ok());
+        config
+    }
+
+    /// Grows the second snake up from its start to (4, 1), while the first
+    /// comes right to (3, 3), next to the other's tail.
+    fn tail_chase(apple: IVec2) -> Versus {
+        let mut versus = Versus::new(1, config(apple));
+        versus.players[1].grow_buffer = 2;
+        let inputs = [Some(Direction::RIGHT), Some(Direction::UP)];
+        assert_eq!(versus.step(inputs), StepOutcome::Moved);
+        assert_eq!(versus.step([None, None]), StepOutcome::Moved);
+        assert_eq!(versus.players[0].snake.head(), IVec2::new(3, 3));
+        assert_eq!(versus.players[1].snake.tail(), IVec2::new(4, 3));
+        assert_eq!(versus.players[1].snake.head(), IVec2::new(4, 1));
+        versus
+    }
+
+    #[test]
+    fn a_rival_tail_moving_away_is_free() {
+        let mut versus = tail_chase(IVec2::new(0, 0));
+        assert_eq!(versus.step([None, None]), StepOutcome::Moved);
+        assert_eq!(versus.players[0].snake.head(), IVec2::new(4, 3));
+        assert_eq!(versus.players[1].snake.tail(), IVec2::new(4, 2));
+    }
+
+    #[test]
+    fn a_rival_tail_stays_put_when_its_snake_eats() {
+        let mut versus = tail_chase(IVec2::new(4, 0));
+        assert_eq!(versus.step([None, None]), StepOutcome::Died);
+        assert_eq!(versus.players[0].death, Some(DeathCause::Rival));
+        assert_eq!(versus.players[1].death, None);
+        assert_eq!(versus.result, Some(VersusResult::Winner(1)));
+    }
+}